@@ -0,0 +1,8 @@
+pub mod chunk;
+pub mod compiler;
+pub mod disassembler;
+pub mod error;
+pub mod heap;
+pub mod object;
+pub mod value;
+pub mod vm;
@@ -0,0 +1,98 @@
+use crate::bytecode::value::Value;
+use crate::lexical_analysis::span::Span;
+
+/// The instructions of the VM. Operands naming a constant are indices into
+/// the constant pool of the chunk the instruction is in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpCode {
+    Constant(usize),
+    Nil,
+    True,
+    False,
+    Pop,
+    /// Reads a local variable from its slot in the current call frame.
+    GetLocal(u8),
+    SetLocal(u8),
+    /// Pops the top of the stack into the global named by the constant.
+    DefineGlobal(usize),
+    GetGlobal(usize),
+    SetGlobal(usize),
+    /// Reads a variable captured by the running closure.
+    GetUpvalue(u8),
+    SetUpvalue(u8),
+    GetProperty(usize),
+    SetProperty(usize),
+    /// Pops a superclass and binds its method named by the constant to the
+    /// instance below it.
+    GetSuper(usize),
+    Equal,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    /// Converts the value on top of the stack to a string, the way `print`
+    /// shows it.
+    Stringify,
+    Print,
+    /// Continues execution at the given offset.
+    Jump(usize),
+    /// Jumps to the given offset if the top of the stack is falsey, leaving
+    /// it on the stack.
+    JumpIfFalse(usize),
+    /// Calls the value below the given number of arguments.
+    Call(u8),
+    /// Creates a closure of the function constant, capturing the variables
+    /// its upvalue descriptors point at.
+    Closure(usize),
+    /// Moves the local on top of the stack into the upvalues capturing it,
+    /// then pops it.
+    CloseUpvalue,
+    Return,
+    Class(usize),
+    /// Copies the methods of the superclass below the top of the stack into
+    /// the subclass on top, and pops the subclass.
+    Inherit,
+    /// Pops a method and adds it to the class below it.
+    Method(usize),
+}
+
+/// A sequence of instructions, along with the constants they use and the
+/// source location each one was compiled from
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<OpCode>,
+    pub constants: Vec<Value>,
+    /// The span of source code each instruction was compiled from, which
+    /// gives the line table as well as locations for runtime errors.
+    spans: Vec<Span>,
+}
+
+impl Chunk {
+    /// Appends an instruction, returning its offset
+    pub fn write(&mut self, op: OpCode, span: Span) -> usize {
+        self.code.push(op);
+        self.spans.push(span);
+        self.code.len() - 1
+    }
+
+    /// Adds a value to the constant pool, returning its index
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    pub fn span(&self, offset: usize) -> Span {
+        self.spans[offset]
+    }
+
+    /// The source line the instruction at the given offset was compiled from
+    pub fn line(&self, offset: usize) -> usize {
+        self.spans[offset].line()
+    }
+}
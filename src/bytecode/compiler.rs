@@ -0,0 +1,715 @@
+use crate::bytecode::chunk::{Chunk, OpCode};
+use crate::bytecode::error::{CompileError, CompileErrorType};
+use crate::bytecode::heap::{Heap, ObjRef};
+use crate::bytecode::object::{Function, Object, UpvalueDescriptor};
+use crate::bytecode::value::Value;
+use crate::lexical_analysis::span::Span;
+use crate::lexical_analysis::symbol::Symbol;
+use crate::lexical_analysis::token::{Literal, Token};
+use crate::lexical_analysis::token_type::TokenType;
+use crate::syntax_analysis::expression::Expr;
+use crate::syntax_analysis::statement::{self, Stmt};
+use std::rc::Rc;
+
+/// The number of local variables a function may have, including its
+/// parameters and the slot holding the function itself
+const MAX_LOCALS: usize = 256;
+
+/// The number of variables a function may capture from functions around it
+const MAX_UPVALUES: usize = 256;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local {
+    name: Symbol,
+    /// The depth of the scope the local was declared in.
+    depth: usize,
+    /// Whether a closure captures the local, so that it has to be moved off
+    /// the stack when its scope ends.
+    is_captured: bool,
+}
+
+/// The state of one function being compiled. Function declarations push a
+/// new one of these, so the innermost function is always the last.
+struct FunctionState {
+    kind: FunctionKind,
+    name: Option<String>,
+    arity: usize,
+    chunk: Chunk,
+    /// The locals in scope, in the order of their stack slots.
+    locals: Vec<Local>,
+    /// The variables captured from enclosing functions so far.
+    upvalues: Vec<UpvalueDescriptor>,
+    scope_depth: usize,
+}
+
+/// Where a variable lives at runtime
+enum Place {
+    Local(u8),
+    /// A variable captured from an enclosing function.
+    Upvalue(u8),
+    Global,
+}
+
+/// Compiles resolved statements into bytecode for the VM. Like the resolver,
+/// the compiler works out for every variable reference whether it is a local,
+/// but it assigns locals to stack slots instead of environments, and records
+/// which locals of enclosing functions each function captures.
+pub struct Compiler<'a> {
+    /// The heap functions and string constants are allocated on.
+    heap: &'a mut Heap,
+    functions: Vec<FunctionState>,
+    errors: Vec<CompileError>,
+    /// The span of the token most recently compiled, which the next
+    /// instructions are attributed to.
+    span: Span,
+}
+
+impl<'a> Compiler<'a> {
+    pub fn new(heap: &'a mut Heap) -> Self {
+        Self {
+            heap,
+            functions: Vec::new(),
+            errors: Vec::new(),
+            span: Span::default(),
+        }
+    }
+
+    /// Compiles a whole program into the function the VM runs as its top level
+    pub fn compile(mut self, statements: &[Stmt]) -> Result<ObjRef, Vec<CompileError>> {
+        self.begin_function(FunctionKind::Script, None);
+        for stmt in statements {
+            self.statement(stmt);
+        }
+        let script = self.end_function();
+
+        if self.errors.is_empty() {
+            Ok(script)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block { statements } => {
+                self.begin_scope();
+                for stmt in statements {
+                    self.statement(stmt);
+                }
+                self.end_scope();
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
+                self.span = name.span();
+                let name_constant = self.identifier_constant(name.symbol());
+                self.emit(OpCode::Class(name_constant));
+                self.define_variable(name);
+
+                // Methods find the superclass in a scope of its own around them
+                if let Some(superclass) = superclass {
+                    self.get_variable(superclass.name.symbol(), superclass.name.span());
+
+                    self.begin_scope();
+                    self.add_local(Symbol::SUPER);
+
+                    self.get_variable(name.symbol(), name.span());
+                    self.span = superclass.name.span();
+                    self.emit(OpCode::Inherit);
+                }
+
+                self.get_variable(name.symbol(), name.span());
+                for method in methods {
+                    let kind = if method.name.symbol() == Symbol::INIT {
+                        FunctionKind::Initializer
+                    } else {
+                        FunctionKind::Method
+                    };
+
+                    self.function(method, kind);
+                    let name_constant = self.identifier_constant(method.name.symbol());
+                    self.emit(OpCode::Method(name_constant));
+                }
+                self.emit(OpCode::Pop);
+
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+            }
+            Stmt::Expression { expr } => {
+                self.expression(expr);
+                self.emit(OpCode::Pop);
+            }
+            Stmt::Function(function) => {
+                // A local function takes its slot before its body is compiled,
+                // so that the body can refer to it
+                if self.state().scope_depth > 0 {
+                    self.declare_local(&function.name);
+                    self.function(function, FunctionKind::Function);
+                } else {
+                    self.function(function, FunctionKind::Function);
+                    self.define_variable(&function.name);
+                }
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition);
+
+                let then_jump = self.emit(OpCode::JumpIfFalse(usize::MAX));
+                self.emit(OpCode::Pop);
+                self.statement(then_branch);
+                let else_jump = self.emit(OpCode::Jump(usize::MAX));
+
+                self.patch_jump(then_jump);
+                self.emit(OpCode::Pop);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+                self.patch_jump(else_jump);
+            }
+            Stmt::Print { expr } => {
+                self.expression(expr);
+                self.emit(OpCode::Print);
+            }
+            Stmt::Return { keyword, value } => {
+                self.span = keyword.span();
+                match value {
+                    Some(value) => {
+                        self.expression(value);
+                        self.emit(OpCode::Return);
+                    }
+                    None => self.emit_implicit_return(),
+                }
+            }
+            Stmt::Var { name, initializer } => {
+                match initializer {
+                    Some(initializer) => self.expression(initializer),
+                    None => {
+                        self.emit(OpCode::Nil);
+                    }
+                }
+
+                self.define_variable(name);
+            }
+            Stmt::While { condition, body } => {
+                let loop_start = self.state().chunk.code.len();
+                self.expression(condition);
+
+                let exit_jump = self.emit(OpCode::JumpIfFalse(usize::MAX));
+                self.emit(OpCode::Pop);
+                self.statement(body);
+                self.emit(OpCode::Jump(loop_start));
+
+                self.patch_jump(exit_jump);
+                self.emit(OpCode::Pop);
+            }
+        }
+    }
+
+    /// Compiles a function declaration into a function object, and emits the
+    /// instruction creating a closure of it
+    fn function(&mut self, declaration: &statement::Function, kind: FunctionKind) {
+        self.begin_function(kind, Some(declaration.name.lexeme.to_string()));
+
+        self.state_mut().arity = declaration.arguments.len();
+        self.begin_scope();
+        for parameter in &declaration.arguments {
+            self.declare_local(parameter);
+        }
+
+        for stmt in &declaration.body {
+            self.statement(stmt);
+        }
+
+        let function = self.end_function();
+        self.span = declaration.name.span();
+        let index = self.state_mut().chunk.add_constant(Value::Object(function));
+        self.emit(OpCode::Closure(index));
+    }
+
+    fn expression(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Assign { name, value, .. } => {
+                self.expression(value);
+                self.span = name.span();
+
+                let op = match self.resolve(name.symbol()) {
+                    Place::Local(slot) => OpCode::SetLocal(slot),
+                    Place::Upvalue(index) => OpCode::SetUpvalue(index),
+                    Place::Global => OpCode::SetGlobal(self.identifier_constant(name.symbol())),
+                };
+                self.emit(op);
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(left);
+                self.expression(right);
+                self.span = operator.span();
+
+                match operator.typ() {
+                    TokenType::BangEqual => {
+                        self.emit(OpCode::Equal);
+                        self.emit(OpCode::Not)
+                    }
+                    TokenType::EqualEqual => self.emit(OpCode::Equal),
+                    TokenType::Greater => self.emit(OpCode::Greater),
+                    TokenType::GreaterEqual => self.emit(OpCode::GreaterEqual),
+                    TokenType::Less => self.emit(OpCode::Less),
+                    TokenType::LessEqual => self.emit(OpCode::LessEqual),
+                    TokenType::Plus => self.emit(OpCode::Add),
+                    TokenType::Minus => self.emit(OpCode::Subtract),
+                    TokenType::Star => self.emit(OpCode::Multiply),
+                    TokenType::Slash => self.emit(OpCode::Divide),
+                    _ => unreachable!("invalid binary operator {}", operator.lexeme),
+                };
+            }
+            Expr::Call {
+                callee,
+                parenthesis,
+                arguments,
+            } => {
+                self.expression(callee);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+
+                // The parser allows no more than 255 arguments
+                self.span = parenthesis.span();
+                self.emit(OpCode::Call(arguments.len() as u8));
+            }
+            Expr::Get { object, name } => {
+                self.expression(object);
+                self.span = name.span();
+                let name_constant = self.identifier_constant(name.symbol());
+                self.emit(OpCode::GetProperty(name_constant));
+            }
+            Expr::Group { expression } => self.expression(expression),
+            Expr::Interpolation { parts, span } => {
+                for (i, part) in parts.iter().enumerate() {
+                    self.expression(part);
+                    self.span = *span;
+
+                    // The parts of the string itself need no conversion
+                    if !matches!(
+                        part,
+                        Expr::Literal {
+                            value: Some(Literal::String(_)),
+                            ..
+                        }
+                    ) {
+                        self.emit(OpCode::Stringify);
+                    }
+                    if i > 0 {
+                        self.emit(OpCode::Add);
+                    }
+                }
+            }
+            Expr::Literal { value, span } => {
+                self.span = *span;
+
+                match value {
+                    Some(Literal::Number(n)) => self.emit_constant(Value::Number(*n)),
+                    Some(Literal::String(s)) => {
+                        let string = self.heap.intern(s);
+                        self.emit_constant(Value::Object(string));
+                    }
+                    Some(Literal::Boolean(true)) => {
+                        self.emit(OpCode::True);
+                    }
+                    Some(Literal::Boolean(false)) => {
+                        self.emit(OpCode::False);
+                    }
+                    Some(Literal::Nil) | None => {
+                        self.emit(OpCode::Nil);
+                    }
+                }
+            }
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(left);
+                self.span = operator.span();
+
+                // Skip the right operand if the left one decides the result,
+                // leaving the left operand as the value
+                let end_jump = if operator.typ() == TokenType::Or {
+                    let else_jump = self.emit(OpCode::JumpIfFalse(usize::MAX));
+                    let end_jump = self.emit(OpCode::Jump(usize::MAX));
+                    self.patch_jump(else_jump);
+                    end_jump
+                } else {
+                    self.emit(OpCode::JumpIfFalse(usize::MAX))
+                };
+
+                self.emit(OpCode::Pop);
+                self.expression(right);
+                self.patch_jump(end_jump);
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                self.expression(object);
+                self.expression(value);
+                self.span = name.span();
+                let name_constant = self.identifier_constant(name.symbol());
+                self.emit(OpCode::SetProperty(name_constant));
+            }
+            Expr::Super {
+                keyword, method, ..
+            } => {
+                self.get_variable(Symbol::THIS, keyword.span());
+                self.get_variable(Symbol::SUPER, keyword.span());
+                self.span = method.span();
+                let name_constant = self.identifier_constant(method.symbol());
+                self.emit(OpCode::GetSuper(name_constant));
+            }
+            Expr::This { keyword, .. } => self.get_variable(Symbol::THIS, keyword.span()),
+            Expr::Unary { operator, right } => {
+                self.expression(right);
+                self.span = operator.span();
+
+                match operator.typ() {
+                    TokenType::Bang => self.emit(OpCode::Not),
+                    TokenType::Minus => self.emit(OpCode::Negate),
+                    _ => unreachable!("invalid unary operator {}", operator.lexeme),
+                };
+            }
+            Expr::Variable { name } => self.get_variable(name.name.symbol(), name.name.span()),
+        }
+    }
+
+    fn get_variable(&mut self, name: Symbol, span: Span) {
+        self.span = span;
+
+        let op = match self.resolve(name) {
+            Place::Local(slot) => OpCode::GetLocal(slot),
+            Place::Upvalue(index) => OpCode::GetUpvalue(index),
+            Place::Global => OpCode::GetGlobal(self.identifier_constant(name)),
+        };
+        self.emit(op);
+    }
+
+    /// Looks a variable up in the scopes of the current function, then in
+    /// those of the functions around it, treating it as a global if it is not
+    /// declared in any of them
+    fn resolve(&mut self, name: Symbol) -> Place {
+        let current = self.functions.len() - 1;
+
+        if let Some(slot) = Self::resolve_local(&self.functions[current], name) {
+            Place::Local(slot)
+        } else if let Some(index) = self.resolve_upvalue(current, name) {
+            Place::Upvalue(index)
+        } else {
+            Place::Global
+        }
+    }
+
+    /// Finds a variable in the functions enclosing the given one, adding an
+    /// upvalue for it to every function in between
+    fn resolve_upvalue(&mut self, function: usize, name: Symbol) -> Option<u8> {
+        let enclosing = function.checked_sub(1)?;
+
+        if let Some(slot) = Self::resolve_local(&self.functions[enclosing], name) {
+            self.functions[enclosing].locals[slot as usize].is_captured = true;
+            return Some(self.add_upvalue(function, true, slot, name));
+        }
+
+        let index = self.resolve_upvalue(enclosing, name)?;
+        Some(self.add_upvalue(function, false, index, name))
+    }
+
+    fn add_upvalue(&mut self, function: usize, is_local: bool, index: u8, name: Symbol) -> u8 {
+        let descriptor = UpvalueDescriptor { is_local, index };
+        let upvalues = &self.functions[function].upvalues;
+
+        if let Some(existing) = upvalues.iter().position(|&upvalue| upvalue == descriptor) {
+            return existing as u8;
+        }
+
+        if upvalues.len() >= MAX_UPVALUES {
+            self.error(CompileErrorType::TooManyUpvalues, name.to_string());
+            return 0;
+        }
+
+        let upvalues = &mut self.functions[function].upvalues;
+        upvalues.push(descriptor);
+        (upvalues.len() - 1) as u8
+    }
+
+    fn resolve_local(function: &FunctionState, name: Symbol) -> Option<u8> {
+        function
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u8)
+    }
+
+    /// Binds the value on top of the stack to a newly declared variable
+    fn define_variable(&mut self, name: &Token) {
+        if self.state().scope_depth > 0 {
+            // The value already sits in the new local's stack slot
+            self.declare_local(name);
+        } else {
+            self.span = name.span();
+            let name_constant = self.identifier_constant(name.symbol());
+            self.emit(OpCode::DefineGlobal(name_constant));
+        }
+    }
+
+    fn declare_local(&mut self, name: &Token) {
+        self.span = name.span();
+        self.add_local(name.symbol());
+    }
+
+    fn add_local(&mut self, name: Symbol) {
+        if self.state().locals.len() >= MAX_LOCALS {
+            self.error(CompileErrorType::TooManyLocals, name.to_string());
+            return;
+        }
+
+        let depth = self.state().scope_depth;
+        self.state_mut().locals.push(Local {
+            name,
+            depth,
+            is_captured: false,
+        });
+    }
+
+    fn begin_scope(&mut self) {
+        self.state_mut().scope_depth += 1;
+    }
+
+    /// Leaves a scope, popping its locals off the stack and moving captured
+    /// ones into their upvalues
+    fn end_scope(&mut self) {
+        let span = self.span;
+        let state = self.state_mut();
+        state.scope_depth -= 1;
+
+        while state
+            .locals
+            .last()
+            .is_some_and(|local| local.depth > state.scope_depth)
+        {
+            let local = state.locals.pop().expect("checked there is a local");
+            let op = if local.is_captured {
+                OpCode::CloseUpvalue
+            } else {
+                OpCode::Pop
+            };
+            state.chunk.write(op, span);
+        }
+    }
+
+    fn begin_function(&mut self, kind: FunctionKind, name: Option<String>) {
+        // Slot zero holds the function being called, or `this` in methods
+        let receiver = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => Symbol::THIS,
+            FunctionKind::Script | FunctionKind::Function => Symbol::intern(""),
+        };
+
+        self.functions.push(FunctionState {
+            kind,
+            name,
+            arity: 0,
+            chunk: Chunk::default(),
+            locals: vec![Local {
+                name: receiver,
+                depth: 0,
+                is_captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+        });
+    }
+
+    /// Finishes the innermost function, allocating it on the heap
+    fn end_function(&mut self) -> ObjRef {
+        self.emit_implicit_return();
+
+        let state = self.functions.pop().expect("a function is being compiled");
+        self.heap.allocate(Object::Function(Function {
+            name: state.name,
+            arity: state.arity,
+            chunk: Rc::new(state.chunk),
+            upvalues: state.upvalues,
+        }))
+    }
+
+    /// Returns from a function without a value, which hands back `this` from
+    /// an initializer and `nil` from anything else
+    fn emit_implicit_return(&mut self) {
+        if self.state().kind == FunctionKind::Initializer {
+            self.emit(OpCode::GetLocal(0));
+        } else {
+            self.emit(OpCode::Nil);
+        }
+
+        self.emit(OpCode::Return);
+    }
+
+    fn identifier_constant(&mut self, name: Symbol) -> usize {
+        let string = self.heap.intern(name.as_str());
+        self.state_mut().chunk.add_constant(Value::Object(string))
+    }
+
+    fn emit_constant(&mut self, value: Value) {
+        let index = self.state_mut().chunk.add_constant(value);
+        self.emit(OpCode::Constant(index));
+    }
+
+    fn emit(&mut self, op: OpCode) -> usize {
+        let span = self.span;
+        self.state_mut().chunk.write(op, span)
+    }
+
+    /// Points a previously emitted jump at the next instruction
+    fn patch_jump(&mut self, offset: usize) {
+        let chunk = &mut self.state_mut().chunk;
+        let target = chunk.code.len();
+
+        match &mut chunk.code[offset] {
+            OpCode::Jump(destination) | OpCode::JumpIfFalse(destination) => *destination = target,
+            op => unreachable!("expected a jump, found {:?}", op),
+        }
+    }
+
+    fn error(&mut self, typ: CompileErrorType, lexeme: String) {
+        self.errors.push(CompileError::new(typ, lexeme, self.span));
+    }
+
+    fn state(&self) -> &FunctionState {
+        self.functions.last().expect("a function is being compiled")
+    }
+
+    fn state_mut(&mut self) -> &mut FunctionState {
+        self.functions
+            .last_mut()
+            .expect("a function is being compiled")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lexical_analysis::lexer::Lexer;
+    use crate::syntax_analysis::parser::Parser;
+
+    fn compile(source: &str) -> (Heap, Result<ObjRef, Vec<CompileError>>) {
+        let mut lexer = Lexer::new(source);
+        let (tokens, errors) = lexer.scan_tokens();
+        assert!(errors.is_empty(), "Unexpected lexical errors: {:?}", errors);
+
+        let (statements, errors) = Parser::new(tokens).parse();
+        assert!(errors.is_empty(), "Unexpected parse errors: {:?}", errors);
+
+        let mut heap = Heap::default();
+        let result = Compiler::new(&mut heap).compile(&statements);
+        (heap, result)
+    }
+
+    #[test]
+    fn test_locals_use_stack_slots() {
+        let (heap, script) = compile("var a = 1; { var b = a; { var c = b; } print b; }");
+        let chunk = &heap.function(script.unwrap()).chunk;
+
+        assert_eq!(
+            chunk.code,
+            vec![
+                OpCode::Constant(0),
+                OpCode::DefineGlobal(1),
+                OpCode::GetGlobal(2),
+                OpCode::GetLocal(1),
+                OpCode::Pop,
+                OpCode::GetLocal(1),
+                OpCode::Print,
+                OpCode::Pop,
+                OpCode::Nil,
+                OpCode::Return,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_jumps_are_patched() {
+        let (heap, script) = compile("while (a) print 1;");
+        let chunk = &heap.function(script.unwrap()).chunk;
+
+        assert_eq!(chunk.code[1], OpCode::JumpIfFalse(6));
+        assert_eq!(chunk.code[5], OpCode::Jump(0));
+        assert_eq!(chunk.code[6], OpCode::Pop);
+    }
+
+    #[test]
+    fn test_captured_variables() {
+        let (heap, script) =
+            compile("fun outer(a, b) { fun middle() { fun inner() { return b + a + b; } } }");
+
+        // Finds the function declared in the given function
+        let nested = |function: ObjRef| {
+            heap.function(function)
+                .chunk
+                .constants
+                .iter()
+                .find_map(|constant| match *constant {
+                    Value::Object(object) => match heap.get(object) {
+                        Object::Function(_) => Some(object),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .unwrap()
+        };
+
+        let outer = nested(script.unwrap());
+        let middle = nested(outer);
+        let inner = nested(middle);
+
+        let upvalue = |is_local, index| UpvalueDescriptor { is_local, index };
+        assert_eq!(
+            heap.function(middle).upvalues,
+            vec![upvalue(true, 2), upvalue(true, 1)]
+        );
+        assert_eq!(
+            heap.function(inner).upvalues,
+            vec![upvalue(false, 0), upvalue(false, 1)]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let locals = (0..MAX_LOCALS)
+            .map(|i| format!("var a{};", i))
+            .collect::<String>();
+        let (_, result) = compile(&format!("{{ {} }}", locals));
+
+        assert_eq!(
+            result
+                .unwrap_err()
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>(),
+            vec!["[line 1] Error at 'a255': Too many local variables in function."]
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use crate::bytecode::chunk::{Chunk, OpCode};
+use crate::bytecode::heap::{Heap, ObjRef};
+use crate::bytecode::object::Object;
+use crate::bytecode::value::Value;
+
+/// Lists the instructions of a compiled function, followed by those of every
+/// function declared inside it, in the style of:
+///
+/// ```text
+/// == <script> ==
+/// 0000    1 CONSTANT            0 '1'
+/// 0001    | DEFINE_GLOBAL       1 'a'
+/// ```
+pub fn disassemble(heap: &Heap, function: ObjRef) -> String {
+    let function = heap.function(function);
+    let chunk = &function.chunk;
+
+    let mut output = format!("== {} ==\n", function);
+    for offset in 0..chunk.code.len() {
+        output += &disassemble_instruction(heap, chunk, offset);
+        output.push('\n');
+    }
+
+    for constant in &chunk.constants {
+        if let Value::Object(object) = *constant {
+            if let Object::Function(_) = heap.get(object) {
+                output.push('\n');
+                output += &disassemble(heap, object);
+            }
+        }
+    }
+
+    output
+}
+
+/// Formats one instruction as its offset, source line, name and operand. The
+/// line is left out when it is the same as the previous instruction's.
+pub fn disassemble_instruction(heap: &Heap, chunk: &Chunk, offset: usize) -> String {
+    let line = if offset > 0 && chunk.line(offset) == chunk.line(offset - 1) {
+        "   |".to_string()
+    } else {
+        format!("{:4}", chunk.line(offset))
+    };
+
+    let constant =
+        |index: usize| format!("{:4} '{}'", index, heap.stringify(chunk.constants[index]));
+
+    // A closure is followed by where each of its captured variables comes from
+    let closure = |index: usize| {
+        let Value::Object(function) = chunk.constants[index] else {
+            unreachable!("closures are always made of functions");
+        };
+
+        heap.function(function)
+            .upvalues
+            .iter()
+            .fold(constant(index), |operand, upvalue| {
+                let source = if upvalue.is_local { "local" } else { "upvalue" };
+                format!(
+                    "{}\n{:04}    |                     {} {}",
+                    operand, offset, source, upvalue.index
+                )
+            })
+    };
+
+    let (name, operand) = match chunk.code[offset] {
+        OpCode::Constant(index) => ("CONSTANT", constant(index)),
+        OpCode::Nil => ("NIL", String::new()),
+        OpCode::True => ("TRUE", String::new()),
+        OpCode::False => ("FALSE", String::new()),
+        OpCode::Pop => ("POP", String::new()),
+        OpCode::GetLocal(slot) => ("GET_LOCAL", format!("{:4}", slot)),
+        OpCode::SetLocal(slot) => ("SET_LOCAL", format!("{:4}", slot)),
+        OpCode::DefineGlobal(index) => ("DEFINE_GLOBAL", constant(index)),
+        OpCode::GetGlobal(index) => ("GET_GLOBAL", constant(index)),
+        OpCode::SetGlobal(index) => ("SET_GLOBAL", constant(index)),
+        OpCode::GetUpvalue(index) => ("GET_UPVALUE", format!("{:4}", index)),
+        OpCode::SetUpvalue(index) => ("SET_UPVALUE", format!("{:4}", index)),
+        OpCode::GetProperty(index) => ("GET_PROPERTY", constant(index)),
+        OpCode::SetProperty(index) => ("SET_PROPERTY", constant(index)),
+        OpCode::GetSuper(index) => ("GET_SUPER", constant(index)),
+        OpCode::Equal => ("EQUAL", String::new()),
+        OpCode::Greater => ("GREATER", String::new()),
+        OpCode::GreaterEqual => ("GREATER_EQUAL", String::new()),
+        OpCode::Less => ("LESS", String::new()),
+        OpCode::LessEqual => ("LESS_EQUAL", String::new()),
+        OpCode::Add => ("ADD", String::new()),
+        OpCode::Subtract => ("SUBTRACT", String::new()),
+        OpCode::Multiply => ("MULTIPLY", String::new()),
+        OpCode::Divide => ("DIVIDE", String::new()),
+        OpCode::Not => ("NOT", String::new()),
+        OpCode::Negate => ("NEGATE", String::new()),
+        OpCode::Stringify => ("STRINGIFY", String::new()),
+        OpCode::Print => ("PRINT", String::new()),
+        OpCode::Jump(target) => ("JUMP", format!("   -> {:04}", target)),
+        OpCode::JumpIfFalse(target) => ("JUMP_IF_FALSE", format!("   -> {:04}", target)),
+        OpCode::Call(argument_count) => ("CALL", format!("{:4}", argument_count)),
+        OpCode::Closure(index) => ("CLOSURE", closure(index)),
+        OpCode::CloseUpvalue => ("CLOSE_UPVALUE", String::new()),
+        OpCode::Return => ("RETURN", String::new()),
+        OpCode::Class(index) => ("CLASS", constant(index)),
+        OpCode::Inherit => ("INHERIT", String::new()),
+        OpCode::Method(index) => ("METHOD", constant(index)),
+    };
+
+    format!("{:04} {} {:<16} {}", offset, line, name, operand)
+        .trim_end()
+        .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bytecode::compiler::Compiler;
+    use crate::lexical_analysis::lexer::Lexer;
+    use crate::syntax_analysis::parser::Parser;
+
+    fn compile_and_disassemble(source: &str) -> String {
+        let mut lexer = Lexer::new(source);
+        let (tokens, _) = lexer.scan_tokens();
+        let (statements, _) = Parser::new(tokens).parse();
+
+        let mut heap = Heap::default();
+        let script = Compiler::new(&mut heap).compile(&statements).unwrap();
+
+        disassemble(&heap, script)
+    }
+
+    #[test]
+    fn test_disassemble() {
+        assert_eq!(
+            compile_and_disassemble("fun f(n) {\n  while (n > 0) n = n - 1;\n}\nf(2);"),
+            "== <script> ==
+0000    1 CLOSURE             0 '<fn f>'
+0001    | DEFINE_GLOBAL       1 'f'
+0002    4 GET_GLOBAL          2 'f'
+0003    | CONSTANT            3 '2'
+0004    | CALL                1
+0005    | POP
+0006    | NIL
+0007    | RETURN
+
+== <fn f> ==
+0000    2 GET_LOCAL           1
+0001    | CONSTANT            0 '0'
+0002    | GREATER
+0003    | JUMP_IF_FALSE       -> 0011
+0004    | POP
+0005    | GET_LOCAL           1
+0006    | CONSTANT            1 '1'
+0007    | SUBTRACT
+0008    | SET_LOCAL           1
+0009    | POP
+0010    | JUMP                -> 0000
+0011    | POP
+0012    | NIL
+0013    | RETURN
+"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_closures() {
+        assert_eq!(
+            compile_and_disassemble("{\n  var a = 1;\n  fun f() { a = a + 1; }\n}"),
+            "== <script> ==
+0000    2 CONSTANT            0 '1'
+0001    3 CLOSURE             1 '<fn f>'
+0001    |                     local 1
+0002    | POP
+0003    | CLOSE_UPVALUE
+0004    | NIL
+0005    | RETURN
+
+== <fn f> ==
+0000    3 GET_UPVALUE         0
+0001    | CONSTANT            0 '1'
+0002    | ADD
+0003    | SET_UPVALUE         0
+0004    | POP
+0005    | NIL
+0006    | RETURN
+"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_branches() {
+        // Short-circuiting jumps leave the condition for the branch to pop
+        assert_eq!(
+            compile_and_disassemble(
+                "if (a and b) print 1; else print 2;\nfor (var i = 0; i < 2 or c; i = i + 1) {}"
+            ),
+            "== <script> ==
+0000    1 GET_GLOBAL          0 'a'
+0001    | JUMP_IF_FALSE       -> 0004
+0002    | POP
+0003    | GET_GLOBAL          1 'b'
+0004    | JUMP_IF_FALSE       -> 0009
+0005    | POP
+0006    | CONSTANT            2 '1'
+0007    | PRINT
+0008    | JUMP                -> 0012
+0009    | POP
+0010    | CONSTANT            3 '2'
+0011    | PRINT
+0012    2 CONSTANT            4 '0'
+0013    | GET_LOCAL           1
+0014    | CONSTANT            5 '2'
+0015    | LESS
+0016    | JUMP_IF_FALSE       -> 0018
+0017    | JUMP                -> 0020
+0018    | POP
+0019    | GET_GLOBAL          6 'c'
+0020    | JUMP_IF_FALSE       -> 0028
+0021    | POP
+0022    | GET_LOCAL           1
+0023    | CONSTANT            7 '1'
+0024    | ADD
+0025    | SET_LOCAL           1
+0026    | POP
+0027    | JUMP                -> 0013
+0028    | POP
+0029    | POP
+0030    | NIL
+0031    | RETURN
+"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_nested_upvalues() {
+        // `middle` captures `b` and `a` so that `inner` can capture them from it
+        assert_eq!(
+            compile_and_disassemble(
+                "fun outer() {\n  var a = 1;\n  var b = 2;\n  fun middle() {\n    fun inner() { return b + a; }\n  }\n}"
+            ),
+            "== <script> ==
+0000    1 CLOSURE             0 '<fn outer>'
+0001    | DEFINE_GLOBAL       1 'outer'
+0002    | NIL
+0003    | RETURN
+
+== <fn outer> ==
+0000    2 CONSTANT            0 '1'
+0001    3 CONSTANT            1 '2'
+0002    4 CLOSURE             2 '<fn middle>'
+0002    |                     local 2
+0002    |                     local 1
+0003    | NIL
+0004    | RETURN
+
+== <fn middle> ==
+0000    5 CLOSURE             0 '<fn inner>'
+0000    |                     upvalue 0
+0000    |                     upvalue 1
+0001    | NIL
+0002    | RETURN
+
+== <fn inner> ==
+0000    5 GET_UPVALUE         0
+0001    | GET_UPVALUE         1
+0002    | ADD
+0003    | RETURN
+0004    | NIL
+0005    | RETURN
+"
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use crate::diagnostics::Diagnostic;
+use crate::lexical_analysis::span::Span;
+
+#[derive(Debug, Clone)]
+pub struct CompileError {
+    typ: CompileErrorType,
+    /// The lexeme of the token the error was found at.
+    lexeme: String,
+    span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum CompileErrorType {
+    TooManyLocals,
+    TooManyUpvalues,
+}
+
+impl CompileError {
+    pub fn new(typ: CompileErrorType, lexeme: String, span: Span) -> Self {
+        Self { typ, lexeme, span }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl CompileErrorType {
+    pub fn code(&self) -> &'static str {
+        match self {
+            CompileErrorType::TooManyLocals => "E400",
+            CompileErrorType::TooManyUpvalues => "E401",
+        }
+    }
+
+    pub fn help(&self) -> Option<&'static str> {
+        match self {
+            CompileErrorType::TooManyLocals | CompileErrorType::TooManyUpvalues => {
+                Some("split the function into smaller ones")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for CompileErrorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileErrorType::TooManyLocals => write!(f, "Too many local variables in function."),
+            CompileErrorType::TooManyUpvalues => {
+                write!(f, "Too many closure variables in function.")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[line {}] Error at '{}': {}",
+            self.span.line(),
+            self.lexeme,
+            self.typ
+        )
+    }
+}
+
+impl From<&CompileError> for Diagnostic {
+    fn from(error: &CompileError) -> Self {
+        Diagnostic::new(error.typ.code(), error.typ.to_string(), error.span())
+            .with_help(error.typ.help())
+    }
+}
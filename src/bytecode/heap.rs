@@ -0,0 +1,334 @@
+use crate::bytecode::chunk::OpCode;
+use crate::bytecode::object::{Class, Closure, Function, Instance, Object, Upvalue};
+use crate::bytecode::value::Value;
+use std::collections::HashMap;
+use std::mem;
+use std::rc::Rc;
+
+/// How many bytes may be allocated before the first collection
+const INITIAL_THRESHOLD: usize = 1024 * 1024;
+
+/// How much the heap may grow, relative to what survived the last
+/// collection, before the next one
+const GROWTH_FACTOR: usize = 2;
+
+/// A handle to an object on the heap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjRef(usize);
+
+/// An object together with its bookkeeping for the collector
+#[derive(Debug)]
+struct Slot {
+    object: Object,
+    marked: bool,
+    /// The estimated size the object was allocated with.
+    size: usize,
+}
+
+/// Owns every object the compiler and VM allocate. Strings are interned, so
+/// two strings with the same text are always the same object.
+///
+/// Objects are freed by a mark-and-sweep collector. The heap cannot know
+/// which objects are in use by itself, so the VM decides when to collect
+/// and passes in its roots.
+#[derive(Debug)]
+pub struct Heap {
+    /// Every object, or `None` for slots which were freed and can be reused.
+    objects: Vec<Option<Slot>>,
+    /// The indices of freed slots.
+    free: Vec<usize>,
+    /// The handle of every string, by its text. Strings are not kept alive
+    /// by being interned.
+    strings: HashMap<Rc<str>, ObjRef>,
+    /// Objects which were marked but whose references have not been traced.
+    gray: Vec<ObjRef>,
+    /// An estimate of the memory taken up by the objects.
+    bytes_allocated: usize,
+    /// How many bytes may be allocated before the next collection.
+    next_gc: usize,
+    /// Whether to collect on every allocation, to flush out objects which
+    /// are in use but not reachable from the roots.
+    stress: bool,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self {
+            objects: Vec::new(),
+            free: Vec::new(),
+            strings: HashMap::new(),
+            gray: Vec::new(),
+            bytes_allocated: 0,
+            next_gc: INITIAL_THRESHOLD,
+            stress: false,
+        }
+    }
+}
+
+impl Heap {
+    pub fn allocate(&mut self, object: Object) -> ObjRef {
+        let size = size_of(&object);
+        self.bytes_allocated += size;
+
+        let slot = Some(Slot {
+            object,
+            marked: false,
+            size,
+        });
+
+        match self.free.pop() {
+            Some(index) => {
+                self.objects[index] = slot;
+                ObjRef(index)
+            }
+            None => {
+                self.objects.push(slot);
+                ObjRef(self.objects.len() - 1)
+            }
+        }
+    }
+
+    /// Returns the string object with the given text, allocating it only if
+    /// there is none yet
+    pub fn intern(&mut self, text: &str) -> ObjRef {
+        if let Some(&string) = self.strings.get(text) {
+            return string;
+        }
+
+        let text = Rc::<str>::from(text);
+        let string = self.allocate(Object::String(Rc::clone(&text)));
+        self.strings.insert(text, string);
+        string
+    }
+
+    pub fn set_stress(&mut self, stress: bool) {
+        self.stress = stress;
+    }
+
+    /// Whether enough has been allocated since the last collection that the
+    /// next allocation should collect first
+    pub fn should_collect(&self) -> bool {
+        self.stress || self.bytes_allocated > self.next_gc
+    }
+
+    /// How many objects are alive
+    #[allow(unused)]
+    pub(crate) fn len(&self) -> usize {
+        self.objects.len() - self.free.len()
+    }
+
+    /// Frees every object which cannot be reached from the given roots.
+    ///
+    /// Marking is tri-colour: white objects are unmarked, gray ones are
+    /// marked and waiting on the gray stack to have their references traced,
+    /// and black ones are marked and traced. Whatever is still white once no
+    /// gray objects are left is garbage.
+    pub fn collect(&mut self, roots: impl IntoIterator<Item = Value>) {
+        for root in roots {
+            self.mark_value(root);
+        }
+
+        while let Some(object) = self.gray.pop() {
+            self.blacken(object);
+        }
+
+        self.sweep();
+        self.next_gc = (self.bytes_allocated * GROWTH_FACTOR).max(INITIAL_THRESHOLD);
+    }
+
+    fn mark_value(&mut self, value: Value) {
+        if let Value::Object(object) = value {
+            self.mark_object(object);
+        }
+    }
+
+    /// Turns a white object gray
+    fn mark_object(&mut self, object: ObjRef) {
+        let slot = self.objects[object.0]
+            .as_mut()
+            .expect("live handles never point at freed slots");
+
+        if !slot.marked {
+            slot.marked = true;
+            self.gray.push(object);
+        }
+    }
+
+    /// Turns a gray object black by marking everything it references
+    fn blacken(&mut self, object: ObjRef) {
+        let mut references = Vec::new();
+
+        match self.get(object) {
+            Object::String(_) => {}
+            Object::Function(function) => references.extend(function.chunk.constants.iter()),
+            Object::Closure(closure) => {
+                references.push(Value::Object(closure.function));
+                references.extend(closure.upvalues.iter().map(|&u| Value::Object(u)));
+            }
+            // Open upvalues point at the stack, which is a root already
+            Object::Upvalue(Upvalue::Open(_)) => {}
+            Object::Upvalue(Upvalue::Closed(value)) => references.push(*value),
+            Object::Class(class) => {
+                for (&name, &method) in &class.methods {
+                    references.push(Value::Object(name));
+                    references.push(Value::Object(method));
+                }
+            }
+            Object::Instance(instance) => {
+                references.push(Value::Object(instance.class));
+                for (&name, &value) in &instance.fields {
+                    references.push(Value::Object(name));
+                    references.push(value);
+                }
+            }
+            Object::BoundMethod(bound) => {
+                references.push(bound.receiver);
+                references.push(Value::Object(bound.method));
+            }
+            Object::Native(_) => {}
+        }
+
+        for reference in references {
+            self.mark_value(reference);
+        }
+    }
+
+    /// Frees every white object and turns the black ones white again
+    fn sweep(&mut self) {
+        for (index, entry) in self.objects.iter_mut().enumerate() {
+            match entry {
+                Some(slot) if slot.marked => slot.marked = false,
+                Some(_) => {
+                    let Some(slot) = mem::take(entry) else {
+                        unreachable!("the slot was just matched");
+                    };
+
+                    if let Object::String(text) = &slot.object {
+                        self.strings.remove(text);
+                    }
+
+                    self.bytes_allocated -= slot.size;
+                    self.free.push(index);
+                }
+                None => {}
+            }
+        }
+    }
+
+    pub fn get(&self, object: ObjRef) -> &Object {
+        match &self.objects[object.0] {
+            Some(slot) => &slot.object,
+            None => unreachable!("live handles never point at freed slots"),
+        }
+    }
+
+    pub fn get_mut(&mut self, object: ObjRef) -> &mut Object {
+        match &mut self.objects[object.0] {
+            Some(slot) => &mut slot.object,
+            None => unreachable!("live handles never point at freed slots"),
+        }
+    }
+
+    /// The text of a string object
+    pub fn string(&self, object: ObjRef) -> &str {
+        match self.get(object) {
+            Object::String(text) => text,
+            other => unreachable!("expected a string, found {:?}", other),
+        }
+    }
+
+    pub fn function(&self, object: ObjRef) -> &Function {
+        match self.get(object) {
+            Object::Function(function) => function,
+            other => unreachable!("expected a function, found {:?}", other),
+        }
+    }
+
+    pub fn closure(&self, object: ObjRef) -> &Closure {
+        match self.get(object) {
+            Object::Closure(closure) => closure,
+            other => unreachable!("expected a closure, found {:?}", other),
+        }
+    }
+
+    pub fn class(&self, object: ObjRef) -> &Class {
+        match self.get(object) {
+            Object::Class(class) => class,
+            other => unreachable!("expected a class, found {:?}", other),
+        }
+    }
+
+    pub fn class_mut(&mut self, object: ObjRef) -> &mut Class {
+        match self.get_mut(object) {
+            Object::Class(class) => class,
+            other => unreachable!("expected a class, found {:?}", other),
+        }
+    }
+
+    /// The instance a value refers to, if it is one
+    pub fn instance(&self, value: Value) -> Option<&Instance> {
+        match value {
+            Value::Object(object) => match self.get(object) {
+                Object::Instance(instance) => Some(instance),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The name of a value's type, as `type()` gives it
+    pub fn type_name(&self, value: Value) -> &'static str {
+        match value {
+            Value::Nil => "nil",
+            Value::Boolean(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::Object(object) => match self.get(object) {
+                Object::String(_) => "string",
+                Object::Class(_) => "class",
+                Object::Instance(_) => "instance",
+                Object::Upvalue(_) => "upvalue",
+                Object::Function(_)
+                | Object::Closure(_)
+                | Object::BoundMethod(_)
+                | Object::Native(_) => "function",
+            },
+        }
+    }
+
+    /// Formats a value the way `print` shows it
+    pub fn stringify(&self, value: Value) -> String {
+        match value {
+            Value::Nil => "nil".to_string(),
+            Value::Boolean(b) => b.to_string(),
+            Value::Number(n) => n.to_string(),
+            Value::Object(object) => match self.get(object) {
+                Object::String(text) => text.to_string(),
+                Object::Function(function) => function.to_string(),
+                Object::Closure(closure) => self.function(closure.function).to_string(),
+                Object::Upvalue(_) => "upvalue".to_string(),
+                Object::Class(class) => class.name.clone(),
+                Object::Instance(instance) => {
+                    format!("{} instance", self.class(instance.class).name)
+                }
+                Object::BoundMethod(bound) => self.stringify(Value::Object(bound.method)),
+                Object::Native(native) => native.to_string(),
+            },
+        }
+    }
+}
+
+/// Estimates how much memory an object takes up, including what it owns
+fn size_of(object: &Object) -> usize {
+    let owned = match object {
+        Object::String(text) => text.len(),
+        Object::Function(function) => {
+            function.chunk.code.len() * mem::size_of::<OpCode>()
+                + function.chunk.constants.len() * mem::size_of::<Value>()
+        }
+        Object::Closure(closure) => closure.upvalues.len() * mem::size_of::<ObjRef>(),
+        Object::Class(class) => class.name.len(),
+        _ => 0,
+    };
+
+    mem::size_of::<Slot>() + owned
+}
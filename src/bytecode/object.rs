@@ -0,0 +1,88 @@
+use crate::bytecode::chunk::Chunk;
+use crate::bytecode::heap::ObjRef;
+use crate::bytecode::value::Value;
+use crate::evaluation::native::Native;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The kinds of objects living on the VM's heap
+#[derive(Debug)]
+pub enum Object {
+    String(Rc<str>),
+    Function(Function),
+    Closure(Closure),
+    Upvalue(Upvalue),
+    Class(Class),
+    Instance(Instance),
+    BoundMethod(BoundMethod),
+    Native(Native),
+}
+
+/// A compiled function, or the top level of a program
+#[derive(Debug)]
+pub struct Function {
+    /// The function's name, or `None` for the top level.
+    pub name: Option<String>,
+    pub arity: usize,
+    pub chunk: Rc<Chunk>,
+    /// Where closures of the function find the variables they capture.
+    pub upvalues: Vec<UpvalueDescriptor>,
+}
+
+/// Where a new closure finds a variable it captures: in a local slot of the
+/// enclosing function if `is_local`, otherwise among the enclosing closure's
+/// own upvalues
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UpvalueDescriptor {
+    pub is_local: bool,
+    pub index: u8,
+}
+
+/// A function together with the variables it captured when it was created.
+/// Every function is called through a closure, even if it captures nothing.
+#[derive(Debug)]
+pub struct Closure {
+    pub function: ObjRef,
+    pub upvalues: Vec<ObjRef>,
+}
+
+/// A variable captured by one or more closures
+#[derive(Debug)]
+pub enum Upvalue {
+    /// The variable is still on the stack, in the given slot.
+    Open(usize),
+    /// The variable's scope has ended, so the upvalue holds it from now on.
+    Closed(Value),
+}
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    /// The closures of the class's methods, including inherited ones, by
+    /// interned name.
+    pub methods: HashMap<ObjRef, ObjRef>,
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub class: ObjRef,
+    /// The instance's fields, by interned name.
+    pub fields: HashMap<ObjRef, Value>,
+}
+
+/// A method together with the instance it was accessed on, which becomes
+/// `this` when it is called
+#[derive(Debug)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: ObjRef,
+}
+
+impl std::fmt::Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "<fn {}>", name),
+            None => write!(f, "<script>"),
+        }
+    }
+}
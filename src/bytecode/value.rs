@@ -0,0 +1,19 @@
+use crate::bytecode::heap::ObjRef;
+
+/// A value on the VM's stack. Anything bigger than a number lives on the
+/// heap and is referred to by its handle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    /// Strings are interned, so comparing handles also compares strings.
+    Object(ObjRef),
+}
+
+impl Value {
+    /// `false` and `nil` are falsey, everything else is truthy
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Boolean(false))
+    }
+}
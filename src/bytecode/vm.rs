@@ -0,0 +1,964 @@
+use crate::bytecode::chunk::{Chunk, OpCode};
+use crate::bytecode::heap::{Heap, ObjRef};
+use crate::bytecode::object::{BoundMethod, Class, Closure, Instance, Object, Upvalue};
+use crate::bytecode::value::Value;
+use crate::evaluation::error::{RuntimeError, RuntimeErrorType, TraceFrame};
+use crate::evaluation::native::{Arguments, Native, NativeValue, STANDARD_LIBRARY};
+use std::collections::HashMap;
+use std::io::{stdout, Write};
+use std::rc::Rc;
+
+/// How many calls may be active at once, counting the top level of the
+/// program, before it is considered to be recursing without end
+const MAX_FRAMES: usize = 256;
+
+/// A function being executed
+struct CallFrame {
+    closure: ObjRef,
+    /// The function's code, shared so that it can be read while the VM's
+    /// state is being changed.
+    chunk: Rc<Chunk>,
+    /// The offset of the next instruction to execute.
+    ip: usize,
+    /// The index of the stack slot holding the function itself, followed by
+    /// its arguments and other locals.
+    base: usize,
+}
+
+/// The arguments of a call to a native, still on the VM's stack
+struct NativeArguments<'a> {
+    heap: &'a Heap,
+    values: &'a [Value],
+}
+
+impl Arguments for NativeArguments<'_> {
+    fn type_name(&self, index: usize) -> &'static str {
+        self.heap.type_name(self.values[index])
+    }
+
+    fn as_number(&self, index: usize) -> Option<f64> {
+        match self.values[index] {
+            Value::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    fn as_string(&self, index: usize) -> Option<&str> {
+        match self.values[index] {
+            Value::Object(object) => match self.heap.get(object) {
+                Object::String(text) => Some(text),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn display(&self, index: usize) -> String {
+        self.heap.stringify(self.values[index])
+    }
+}
+
+/// A stack-based virtual machine which executes the bytecode produced by
+/// the compiler. Globals and heap objects persist across calls to
+/// `interpret`, so one VM can run a REPL session.
+pub struct Vm {
+    heap: Heap,
+    /// Global variables, by interned name.
+    globals: HashMap<ObjRef, Value>,
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    /// The upvalues still pointing at variables on the stack, so that
+    /// closures capturing the same variable share one upvalue.
+    open_upvalues: Vec<ObjRef>,
+    /// The interned name of initializers, "init".
+    init_string: ObjRef,
+    /// Where `print` writes to.
+    output: Box<dyn Write>,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        let mut heap = Heap::default();
+        let init_string = heap.intern("init");
+
+        let mut vm = Self {
+            heap,
+            globals: HashMap::new(),
+            stack: Vec::new(),
+            frames: Vec::new(),
+            open_upvalues: Vec::new(),
+            init_string,
+            output: Box::new(stdout()),
+        };
+
+        for &native in STANDARD_LIBRARY {
+            vm.define_native(native);
+        }
+
+        vm
+    }
+
+    /// Makes a native function available to programs as a global
+    pub fn define_native(&mut self, native: Native) {
+        let name = self.heap.intern(native.name);
+        let native = self.heap.allocate(Object::Native(native));
+        self.globals.insert(name, Value::Object(native));
+    }
+
+    /// Sends the output of `print` somewhere other than stdout
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output;
+    }
+
+    /// Makes every allocation collect garbage first, so that objects which
+    /// are in use but unreachable from the roots are caught early
+    pub fn set_gc_stress(&mut self, stress: bool) {
+        self.heap.set_stress(stress);
+    }
+
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    /// The heap programs have to be compiled onto before they are run
+    pub fn heap_mut(&mut self) -> &mut Heap {
+        &mut self.heap
+    }
+
+    /// Runs the top-level function of a compiled program
+    pub fn interpret(&mut self, script: ObjRef) -> Result<(), RuntimeError> {
+        // Nothing refers to the script yet, so this must not collect
+        let script = self.heap.allocate(Object::Closure(Closure {
+            function: script,
+            upvalues: Vec::new(),
+        }));
+
+        self.push(Value::Object(script));
+        let result = self.call(script, 0).and_then(|()| self.run());
+
+        if result.is_err() {
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+
+        result
+    }
+
+    fn run(&mut self) -> Result<(), RuntimeError> {
+        loop {
+            let frame = self.frames.last_mut().expect("a function is running");
+            let op = frame.chunk.code[frame.ip];
+            frame.ip += 1;
+
+            match op {
+                OpCode::Constant(index) => {
+                    let constant = self.frame().chunk.constants[index];
+                    self.push(constant);
+                }
+                OpCode::Nil => self.push(Value::Nil),
+                OpCode::True => self.push(Value::Boolean(true)),
+                OpCode::False => self.push(Value::Boolean(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal(slot) => {
+                    let value = self.stack[self.frame().base + slot as usize];
+                    self.push(value);
+                }
+                OpCode::SetLocal(slot) => {
+                    let index = self.frame().base + slot as usize;
+                    self.stack[index] = self.peek(0);
+                }
+                OpCode::DefineGlobal(index) => {
+                    let name = self.string_constant(index);
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                OpCode::GetGlobal(index) => {
+                    let name = self.string_constant(index);
+                    match self.globals.get(&name) {
+                        Some(&value) => self.push(value),
+                        None => return Err(self.undefined_variable(name)),
+                    }
+                }
+                OpCode::SetGlobal(index) => {
+                    let name = self.string_constant(index);
+                    if !self.globals.contains_key(&name) {
+                        return Err(self.undefined_variable(name));
+                    }
+                    self.globals.insert(name, self.peek(0));
+                }
+                OpCode::GetUpvalue(index) => {
+                    let upvalue = self.upvalue(index);
+                    let value = match self.heap.get(upvalue) {
+                        Object::Upvalue(Upvalue::Open(slot)) => self.stack[*slot],
+                        Object::Upvalue(Upvalue::Closed(value)) => *value,
+                        other => unreachable!("expected an upvalue, found {:?}", other),
+                    };
+                    self.push(value);
+                }
+                OpCode::SetUpvalue(index) => {
+                    let upvalue = self.upvalue(index);
+                    let value = self.peek(0);
+                    match self.heap.get_mut(upvalue) {
+                        Object::Upvalue(Upvalue::Open(slot)) => self.stack[*slot] = value,
+                        Object::Upvalue(Upvalue::Closed(closed)) => *closed = value,
+                        other => unreachable!("expected an upvalue, found {:?}", other),
+                    }
+                }
+                OpCode::GetProperty(index) => {
+                    let name = self.string_constant(index);
+                    let Some(instance) = self.heap.instance(self.peek(0)) else {
+                        return Err(self.error(RuntimeErrorType::OnlyInstancesHaveProperties));
+                    };
+
+                    // Fields shadow methods
+                    if let Some(&value) = instance.fields.get(&name) {
+                        self.pop();
+                        self.push(value);
+                    } else {
+                        let class = instance.class;
+                        self.bind_method(class, name)?;
+                    }
+                }
+                OpCode::SetProperty(index) => {
+                    let name = self.string_constant(index);
+                    let Value::Object(instance) = self.peek(1) else {
+                        return Err(self.error(RuntimeErrorType::OnlyInstancesHaveFields));
+                    };
+                    let Object::Instance(instance) = self.heap.get_mut(instance) else {
+                        return Err(self.error(RuntimeErrorType::OnlyInstancesHaveFields));
+                    };
+
+                    let value = self.stack[self.stack.len() - 1];
+                    instance.fields.insert(name, value);
+
+                    self.pop();
+                    self.pop();
+                    self.push(value);
+                }
+                OpCode::GetSuper(index) => {
+                    let name = self.string_constant(index);
+                    let Value::Object(superclass) = self.pop() else {
+                        unreachable!("'super' is always bound to a class");
+                    };
+                    self.bind_method(superclass, name)?;
+                }
+                OpCode::Equal => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.push(Value::Boolean(left == right));
+                }
+                OpCode::Greater => self.numeric_binary(|a, b| Value::Boolean(a > b))?,
+                OpCode::GreaterEqual => self.numeric_binary(|a, b| Value::Boolean(a >= b))?,
+                OpCode::Less => self.numeric_binary(|a, b| Value::Boolean(a < b))?,
+                OpCode::LessEqual => self.numeric_binary(|a, b| Value::Boolean(a <= b))?,
+                OpCode::Add => self.add()?,
+                OpCode::Subtract => self.numeric_binary(|a, b| Value::Number(a - b))?,
+                OpCode::Multiply => self.numeric_binary(|a, b| Value::Number(a * b))?,
+                OpCode::Divide => self.numeric_binary(|a, b| Value::Number(a / b))?,
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.push(Value::Boolean(!value.is_truthy()));
+                }
+                OpCode::Negate => {
+                    let Value::Number(n) = self.peek(0) else {
+                        return Err(self.error(RuntimeErrorType::OperandMustBeNumber));
+                    };
+                    self.pop();
+                    self.push(Value::Number(-n));
+                }
+                OpCode::Stringify => {
+                    // The value stays on the stack while the string is
+                    // allocated, in case that collects garbage
+                    let text = self.heap.stringify(self.peek(0));
+                    let string = self.intern(&text);
+                    self.pop();
+                    self.push(Value::Object(string));
+                }
+                OpCode::Print => {
+                    let value = self.pop();
+                    let text = self.heap.stringify(value);
+                    if let Err(error) = writeln!(self.output, "{}", text) {
+                        return Err(self.error(RuntimeErrorType::Output(error.kind())));
+                    }
+                }
+                OpCode::Jump(target) => self.frame_mut().ip = target,
+                OpCode::JumpIfFalse(target) => {
+                    if !self.peek(0).is_truthy() {
+                        self.frame_mut().ip = target;
+                    }
+                }
+                OpCode::Call(argument_count) => {
+                    let argument_count = argument_count as usize;
+                    self.call_value(self.peek(argument_count), argument_count)?;
+                }
+                OpCode::Closure(index) => {
+                    let Value::Object(function) = self.frame().chunk.constants[index] else {
+                        unreachable!("closures are always made of functions");
+                    };
+
+                    let descriptors = self.heap.function(function).upvalues.clone();
+                    let upvalues = descriptors
+                        .iter()
+                        .map(|descriptor| {
+                            if descriptor.is_local {
+                                self.capture_upvalue(self.frame().base + descriptor.index as usize)
+                            } else {
+                                self.upvalue(descriptor.index)
+                            }
+                        })
+                        .collect();
+
+                    let closure = self.allocate(Object::Closure(Closure { function, upvalues }));
+                    self.push(Value::Object(closure));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().expect("a function is running");
+                    self.close_upvalues(frame.base);
+                    self.stack.truncate(frame.base);
+
+                    if self.frames.is_empty() {
+                        return Ok(());
+                    }
+
+                    self.push(result);
+                }
+                OpCode::Class(index) => {
+                    let name = self.string_constant(index);
+                    let class = self.allocate(Object::Class(Class {
+                        name: self.heap.string(name).to_string(),
+                        methods: HashMap::new(),
+                    }));
+                    self.push(Value::Object(class));
+                }
+                OpCode::Inherit => {
+                    let superclass = match self.peek(1) {
+                        Value::Object(object) => match self.heap.get(object) {
+                            Object::Class(superclass) => superclass.methods.clone(),
+                            _ => return Err(self.error(RuntimeErrorType::SuperclassMustBeClass)),
+                        },
+                        _ => return Err(self.error(RuntimeErrorType::SuperclassMustBeClass)),
+                    };
+
+                    let Value::Object(subclass) = self.pop() else {
+                        unreachable!("a class declaration always creates a class");
+                    };
+                    self.heap.class_mut(subclass).methods.extend(superclass);
+                }
+                OpCode::Method(index) => {
+                    let name = self.string_constant(index);
+                    let (Value::Object(method), Value::Object(class)) = (self.pop(), self.peek(0))
+                    else {
+                        unreachable!("methods are always compiled into their class");
+                    };
+                    self.heap.class_mut(class).methods.insert(name, method);
+                }
+            }
+        }
+    }
+
+    /// Calls a function, class or bound method whose arguments are on top of
+    /// the stack
+    fn call_value(&mut self, callee: Value, argument_count: usize) -> Result<(), RuntimeError> {
+        let Value::Object(object) = callee else {
+            return Err(self.error(RuntimeErrorType::NotCallable));
+        };
+
+        // The callee's slot becomes slot zero of the new frame
+        let callee_slot = self.stack.len() - argument_count - 1;
+
+        match self.heap.get(object) {
+            Object::Closure(_) => self.call(object, argument_count),
+            Object::Class(class) => {
+                let initializer = class.methods.get(&self.init_string).copied();
+
+                let instance = self.allocate(Object::Instance(Instance {
+                    class: object,
+                    fields: HashMap::new(),
+                }));
+                self.stack[callee_slot] = Value::Object(instance);
+
+                match initializer {
+                    Some(initializer) => self.call(initializer, argument_count),
+                    None if argument_count != 0 => {
+                        Err(self.error(RuntimeErrorType::ArityMismatch {
+                            expected: 0,
+                            got: argument_count,
+                        }))
+                    }
+                    None => Ok(()),
+                }
+            }
+            Object::BoundMethod(bound) => {
+                let method = bound.method;
+                self.stack[callee_slot] = bound.receiver;
+                self.call(method, argument_count)
+            }
+            Object::Native(native) => {
+                let native = *native;
+                self.call_native(native, argument_count)
+            }
+            _ => Err(self.error(RuntimeErrorType::NotCallable)),
+        }
+    }
+
+    /// Pushes a call frame for a closure whose arguments are on top of the
+    /// stack
+    fn call(&mut self, closure: ObjRef, argument_count: usize) -> Result<(), RuntimeError> {
+        let callee = self.heap.function(self.heap.closure(closure).function);
+
+        if argument_count != callee.arity {
+            return Err(self.error(RuntimeErrorType::ArityMismatch {
+                expected: callee.arity,
+                got: argument_count,
+            }));
+        }
+
+        if self.frames.len() >= MAX_FRAMES {
+            return Err(self.error(RuntimeErrorType::StackOverflow));
+        }
+
+        self.frames.push(CallFrame {
+            closure,
+            chunk: Rc::clone(&callee.chunk),
+            ip: 0,
+            base: self.stack.len() - argument_count - 1,
+        });
+
+        Ok(())
+    }
+
+    /// Calls a native with the arguments on top of the stack, replacing them
+    /// and the native with its result
+    fn call_native(&mut self, native: Native, argument_count: usize) -> Result<(), RuntimeError> {
+        if argument_count != native.arity {
+            return Err(self.error(RuntimeErrorType::ArityMismatch {
+                expected: native.arity,
+                got: argument_count,
+            }));
+        }
+
+        let callee_slot = self.stack.len() - argument_count - 1;
+        let arguments = NativeArguments {
+            heap: &self.heap,
+            values: &self.stack[callee_slot + 1..],
+        };
+
+        let result = match (native.function)(&arguments) {
+            Ok(NativeValue::Nil) => Value::Nil,
+            Ok(NativeValue::Boolean(b)) => Value::Boolean(b),
+            Ok(NativeValue::Number(n)) => Value::Number(n),
+            // The arguments stay on the stack while the string is allocated
+            Ok(NativeValue::String(s)) => Value::Object(self.intern(&s)),
+            Err(typ) => return Err(self.error(typ)),
+        };
+
+        self.stack.truncate(callee_slot);
+        self.push(result);
+        Ok(())
+    }
+
+    /// The running closure's upvalue with the given index
+    fn upvalue(&self, index: u8) -> ObjRef {
+        self.heap.closure(self.frame().closure).upvalues[index as usize]
+    }
+
+    /// Returns the open upvalue for the given stack slot, creating one if no
+    /// closure has captured the slot yet
+    fn capture_upvalue(&mut self, slot: usize) -> ObjRef {
+        let existing = self.open_upvalues.iter().copied().find(|&upvalue| {
+            matches!(self.heap.get(upvalue), Object::Upvalue(Upvalue::Open(open)) if *open == slot)
+        });
+
+        existing.unwrap_or_else(|| {
+            let upvalue = self.allocate(Object::Upvalue(Upvalue::Open(slot)));
+            self.open_upvalues.push(upvalue);
+            upvalue
+        })
+    }
+
+    /// Closes every open upvalue pointing at the given stack slot or above,
+    /// copying the variables off the stack into the upvalues
+    fn close_upvalues(&mut self, from: usize) {
+        let heap = &mut self.heap;
+        let stack = &self.stack;
+
+        self.open_upvalues.retain(|&upvalue| {
+            let Object::Upvalue(state) = heap.get_mut(upvalue) else {
+                unreachable!("open upvalues are always upvalues");
+            };
+
+            match *state {
+                Upvalue::Open(slot) if slot >= from => {
+                    *state = Upvalue::Closed(stack[slot]);
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    /// Replaces the instance on top of the stack with its class's method of
+    /// the given name, bound to the instance
+    fn bind_method(&mut self, class: ObjRef, name: ObjRef) -> Result<(), RuntimeError> {
+        let Some(&method) = self.heap.class(class).methods.get(&name) else {
+            let name = self.heap.string(name).to_string();
+            return Err(self.error(RuntimeErrorType::UndefinedProperty(name)));
+        };
+
+        // The receiver stays on the stack until the bound method is
+        // allocated, so that a collection cannot free it
+        let receiver = self.peek(0);
+        let bound = self.allocate(Object::BoundMethod(BoundMethod { receiver, method }));
+        self.pop();
+        self.push(Value::Object(bound));
+
+        Ok(())
+    }
+
+    fn add(&mut self) -> Result<(), RuntimeError> {
+        match (self.peek(1), self.peek(0)) {
+            (Value::Number(_), Value::Number(_)) => {
+                self.numeric_binary(|a, b| Value::Number(a + b))
+            }
+            (Value::Object(left), Value::Object(right)) => {
+                let (Object::String(left), Object::String(right)) =
+                    (self.heap.get(left), self.heap.get(right))
+                else {
+                    return Err(self.error(RuntimeErrorType::OperandsMustBeNumbersOrStrings));
+                };
+
+                let result = format!("{}{}", left, right);
+                let result = self.intern(&result);
+                self.pop();
+                self.pop();
+                self.push(Value::Object(result));
+                Ok(())
+            }
+            _ => Err(self.error(RuntimeErrorType::OperandsMustBeNumbersOrStrings)),
+        }
+    }
+
+    fn numeric_binary(&mut self, operator: fn(f64, f64) -> Value) -> Result<(), RuntimeError> {
+        let (Value::Number(left), Value::Number(right)) = (self.peek(1), self.peek(0)) else {
+            return Err(self.error(RuntimeErrorType::OperandsMustBeNumbers));
+        };
+
+        self.pop();
+        self.pop();
+        self.push(operator(left, right));
+        Ok(())
+    }
+
+    /// Allocates an object, collecting garbage first if the heap has grown
+    /// enough. Every object in use must be reachable from the roots by then.
+    fn allocate(&mut self, object: Object) -> ObjRef {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+
+        self.heap.allocate(object)
+    }
+
+    fn intern(&mut self, text: &str) -> ObjRef {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+
+        self.heap.intern(text)
+    }
+
+    /// Frees every object which is not reachable from the stack, the
+    /// globals, the running closures or the open upvalues
+    fn collect_garbage(&mut self) {
+        let globals = self
+            .globals
+            .iter()
+            .flat_map(|(&name, &value)| [Value::Object(name), value]);
+        let frames = self.frames.iter().map(|frame| Value::Object(frame.closure));
+        let open_upvalues = self
+            .open_upvalues
+            .iter()
+            .map(|&upvalue| Value::Object(upvalue));
+
+        let roots = self
+            .stack
+            .iter()
+            .copied()
+            .chain(globals)
+            .chain(frames)
+            .chain(open_upvalues)
+            .chain([Value::Object(self.init_string)])
+            .collect::<Vec<_>>();
+
+        self.heap.collect(roots);
+    }
+
+    fn string_constant(&self, index: usize) -> ObjRef {
+        match self.frame().chunk.constants[index] {
+            Value::Object(string) => string,
+            constant => unreachable!("expected a name, found {:?}", constant),
+        }
+    }
+
+    fn undefined_variable(&self, name: ObjRef) -> RuntimeError {
+        let name = self.heap.string(name).to_string();
+        self.error(RuntimeErrorType::UndefinedVariable(name))
+    }
+
+    /// Creates an error at the instruction currently being executed, with a
+    /// trace of the calls leading to it
+    fn error(&self, typ: RuntimeErrorType) -> RuntimeError {
+        // Every frame but the innermost is executing the call to the next one
+        let trace = self
+            .frames
+            .iter()
+            .rev()
+            .map(|frame| {
+                let function = self
+                    .heap
+                    .function(self.heap.closure(frame.closure).function);
+                TraceFrame::new(function.name.clone(), frame.chunk.line(frame.ip - 1))
+            })
+            .collect();
+
+        let frame = self.frame();
+        RuntimeError::new(typ, frame.chunk.span(frame.ip - 1)).with_trace(trace)
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("a function is running")
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("a function is running")
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("the stack is not empty")
+    }
+
+    /// Looks at a value the given distance down from the top of the stack
+    fn peek(&self, distance: usize) -> Value {
+        self.stack[self.stack.len() - 1 - distance]
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bytecode::compiler::Compiler;
+    use crate::lexical_analysis::lexer::Lexer;
+    use crate::semantic_analysis::resolver::Resolver;
+    use crate::syntax_analysis::parser::Parser;
+
+    fn run(vm: &mut Vm, source: &str) -> Result<(), RuntimeError> {
+        let mut lexer = Lexer::new(source);
+        let (tokens, errors) = lexer.scan_tokens();
+        assert!(errors.is_empty(), "Unexpected lexical errors: {:?}", errors);
+
+        let (statements, errors) = Parser::new(tokens).parse();
+        assert!(errors.is_empty(), "Unexpected parse errors: {:?}", errors);
+
+        let (_, errors) = Resolver::new().resolve(&statements);
+        assert!(errors.is_empty(), "Unexpected resolve errors: {:?}", errors);
+
+        let script = Compiler::new(vm.heap_mut())
+            .compile(&statements)
+            .expect("program compiles");
+        vm.interpret(script)
+    }
+
+    /// Formats the value of a global the way `print` would
+    fn global(vm: &mut Vm, name: &str) -> String {
+        let name = vm.heap.intern(name);
+        vm.heap.stringify(vm.globals[&name])
+    }
+
+    fn assert_globals(source: &str, expected: &[(&str, &str)]) {
+        let mut vm = Vm::new();
+        run(&mut vm, source).unwrap();
+
+        for (name, value) in expected {
+            assert_eq!(global(&mut vm, name), *value, "value of '{}'", name);
+        }
+        assert!(vm.stack.is_empty(), "Stack left over: {:?}", vm.stack);
+    }
+
+    /// A writer whose reader has gone away
+    struct ClosedPipe;
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn assert_error(source: &str, expected: &str) {
+        let error = run(&mut Vm::new(), source).unwrap_err();
+        assert_eq!(error.to_string(), expected);
+    }
+
+    #[test]
+    fn test_arithmetic_and_comparison() {
+        assert_globals(
+            "var a = 1 + 2 * 3 - 4 / 2; var b = -a; var c = a >= 5 and !(b > 0);
+             var d = 1 != 2; var e = nil == false;",
+            &[
+                ("a", "5"),
+                ("b", "-5"),
+                ("c", "true"),
+                ("d", "true"),
+                ("e", "false"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_strings_are_interned() {
+        assert_globals(
+            "var a = \"con\" + \"cat\"; var b = a == \"concat\"; var c = nil or \"x\";",
+            &[("a", "concat"), ("b", "true"), ("c", "x")],
+        );
+    }
+
+    #[test]
+    fn test_interpolation() {
+        assert_globals(
+            "var n = 2; var a = \"${n} + ${n} = ${n + n}\"; var b = \"${nil}${a == \"2 + 2 = 4\"}\";",
+            &[("a", "2 + 2 = 4"), ("b", "niltrue")],
+        );
+    }
+
+    #[test]
+    fn test_scopes_and_loops() {
+        assert_globals(
+            "var a = \"global\"; var b; var sum = 0;
+             { var a = \"outer\"; { var a = \"inner\"; b = a; } a = a + \"!\"; b = b + a; }
+             for (var i = 0; i < 5; i = i + 1) { var j = i * 2; if (j > 4) sum = sum + j; }
+             fun count() {
+                 var i = 0;
+                 while (true) { i = i + 1; if (i >= 3) { var done = i; i = done * 10; } if (i > 10) return i; }
+             }
+             var c = count();",
+            &[("a", "global"), ("b", "innerouter!"), ("sum", "14"), ("c", "30")],
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_globals(
+            "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+             fun nothing() {}
+             var a = fib(15); var b = nothing(); var c = fib;",
+            &[("a", "610"), ("b", "nil"), ("c", "<fn fib>")],
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        assert_globals(
+            "fun counter() { var count = 0; fun increment() { count = count + 1; return count; } return increment; }
+             var next = counter(); next(); var a = next(); var b = counter()();
+
+             var get; var set;
+             { var shared = \"before\"; fun g() { return shared; } fun s(v) { shared = v; } get = g; set = s; }
+             set(\"after\"); var c = get();
+
+             var closures = nil;
+             for (var i = 0; i < 3; i = i + 1) { var j = i; fun f() { return j; } if (i == 1) closures = f; }
+             var d = closures();
+
+             fun outer() { var x = \"x\"; fun middle() { fun inner() { return x; } return inner; } return middle()(); }
+             var e = outer();",
+            &[("a", "2"), ("b", "1"), ("c", "after"), ("d", "1"), ("e", "x")],
+        );
+    }
+
+    #[test]
+    fn test_classes_and_methods() {
+        assert_globals(
+            "class Counter {
+                 init(start) { this.count = start; }
+                 increment() { this.count = this.count + 1; return this; }
+             }
+             var counter = Counter(10);
+             var method = counter.increment;
+             method();
+             var a = counter.increment().count;
+             var b = counter;
+             var c = Counter;
+             counter.increment = \"field\";
+             var d = counter.increment;",
+            &[
+                ("a", "12"),
+                ("b", "Counter instance"),
+                ("c", "Counter"),
+                ("d", "field"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_inheritance() {
+        assert_globals(
+            "class A { name() { return \"A\"; } greet() { return \"I am \" + this.name(); } }
+             class B < A { name() { return \"B\"; } parent() { fun get() { return super.name(); } return get(); } }
+             var a = B().greet(); var b = B().parent();",
+            &[("a", "I am B"), ("b", "A")],
+        );
+    }
+
+    #[test]
+    fn test_globals_persist_across_runs() {
+        let mut vm = Vm::new();
+        run(&mut vm, "var a = 1; fun f() { return a + 1; }").unwrap();
+        run(&mut vm, "a = f();").unwrap();
+        assert_eq!(global(&mut vm, "a"), "2");
+
+        // A failing run leaves the VM ready for the next one
+        run(&mut vm, "{ var b = 1; b(); }").unwrap_err();
+        run(&mut vm, "a = a + 1;").unwrap();
+        assert_eq!(global(&mut vm, "a"), "3");
+    }
+
+    #[test]
+    fn test_garbage_collection() {
+        let mut vm = Vm::new();
+        vm.set_gc_stress(true);
+        let initial = vm.heap.len();
+
+        run(
+            &mut vm,
+            "class Node { init(value) { this.value = value; this.self = this; } }
+             fun make(value) { var node = Node(value); fun get() { return node; } node.get = get; return node; }
+             var kept = make(\"kept\");
+             var text = \"\";
+             for (var i = 0; i < 100; i = i + 1) { make(i); text = text + \"x\"; }
+             var value = kept.get().self.value;",
+        )
+        .unwrap();
+
+        assert_eq!(global(&mut vm, "value"), "kept");
+        assert_eq!(global(&mut vm, "text").len(), 100);
+
+        // Only the globals and what they refer to are left
+        vm.collect_garbage();
+        let created = vm.heap.len() - initial;
+        assert!(created < 30, "{} objects left", created);
+    }
+
+    #[test]
+    fn test_natives() {
+        assert_globals(
+            "var a = len(\"naïve\"); var b = str(1.5) + str(nil); var c = num(\"42\") + 1;
+             var d = type(clock) + type(\"\") + type(nil); var e = clock() > 0; var f = len;",
+            &[
+                ("a", "5"),
+                ("b", "1.5nil"),
+                ("c", "43"),
+                ("d", "functionstringnil"),
+                ("e", "true"),
+                ("f", "<native fn len>"),
+            ],
+        );
+        assert_error("len(1, 2);", "Expected 1 arguments but got 2.\n[line 1]");
+        assert_error(
+            "len(nil);",
+            "Expected a string as argument 1 but got a nil.\n[line 1]",
+        );
+    }
+
+    #[test]
+    fn test_stack_traces() {
+        let error = run(
+            &mut Vm::new(),
+            "class Greeter {
+                 greet(name) { return \"Hello \" + name; }
+             }
+             fun welcome(name) {
+                 return Greeter().greet(name);
+             }
+             welcome(\"a\");
+             welcome(1);",
+        )
+        .unwrap_err();
+
+        let trace = error
+            .trace()
+            .iter()
+            .map(|frame| frame.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            trace,
+            [
+                "[line 2] in greet()",
+                "[line 5] in welcome()",
+                "[line 8] in script"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        assert_error("-\"a\";", "Operand must be a number.\n[line 1]");
+        assert_error("1 < \"a\";", "Operands must be numbers.\n[line 1]");
+        assert_error(
+            "1 +\n\"a\";",
+            "Operands must be two numbers or two strings.\n[line 1]",
+        );
+        assert_error("print x;", "Undefined variable 'x'.\n[line 1]");
+        assert_error("x = 1;", "Undefined variable 'x'.\n[line 1]");
+        assert_error("\"f\"();", "Can only call functions and classes.\n[line 1]");
+        assert_error(
+            "fun f(a) {}\nf();",
+            "Expected 1 arguments but got 0.\n[line 2]",
+        );
+        assert_error(
+            "class A {}\nA(1);",
+            "Expected 0 arguments but got 1.\n[line 2]",
+        );
+        assert_error("fun f() { f(); }\nf();", "Stack overflow.\n[line 1]");
+        assert_error("1 .x;", "Only instances have properties.\n[line 1]");
+        assert_error("class A {}\nA().x;", "Undefined property 'x'.\n[line 2]");
+        assert_error(
+            "var A = 1;\nclass B < A {}",
+            "Superclass must be a class.\n[line 2]",
+        );
+    }
+
+    #[test]
+    fn test_output_errors() {
+        let mut vm = Vm::new();
+        vm.set_output(Box::new(ClosedPipe));
+
+        let error = run(&mut vm, "var a = 1;\nprint a;\na = 2;").unwrap_err();
+        assert_eq!(
+            error.typ(),
+            &RuntimeErrorType::Output(std::io::ErrorKind::BrokenPipe)
+        );
+        assert_eq!(error.span().line(), 2);
+        assert_eq!(global(&mut vm, "a"), "1");
+    }
+}
@@ -0,0 +1,306 @@
+use crate::lexical_analysis::span::Span;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// An error from any stage of running a program, in the shape needed to
+/// render it against the source code
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    /// A stable identifier for the kind of error, e.g. "E001".
+    code: &'static str,
+    message: String,
+    span: Span,
+    /// An optional hint on how to fix the error.
+    help: Option<&'static str>,
+    /// The calls which were active when the error happened, innermost first.
+    trace: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(code: &'static str, message: String, span: Span) -> Self {
+        Self {
+            code,
+            message,
+            span,
+            help: None,
+            trace: Vec::new(),
+        }
+    }
+
+    pub fn with_help(mut self, help: Option<&'static str>) -> Self {
+        self.help = help;
+        self
+    }
+
+    pub fn with_trace(mut self, trace: Vec<String>) -> Self {
+        self.trace = trace;
+        self
+    }
+}
+
+/// A one-line summary, for when the source is not at hand to render against
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "error[{}]: {} at {}:{}",
+            self.code,
+            self.message,
+            self.span.line(),
+            self.span.column()
+        )
+    }
+}
+
+/// Renders diagnostics as the offending source line with the error's span
+/// underlined, in the style of:
+///
+/// ```text
+/// error[E001]: Unexpected character: @
+///  --> script.lox:1:9
+///   |
+/// 1 | var a = @;
+///   |         ^
+/// ```
+pub struct Renderer<'a> {
+    source: &'a str,
+    /// The name of the file the source came from, if any.
+    path: Option<&'a str>,
+    /// Whether to use ANSI escape codes to colour the output.
+    colored: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(source: &'a str, path: Option<&'a str>, colored: bool) -> Self {
+        Self {
+            source,
+            path,
+            colored,
+        }
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let span = diagnostic.span;
+        let line_number = span.line().to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        let mut output = format!(
+            "{}error[{}]{}: {}{}{}\n",
+            self.paint(RED),
+            diagnostic.code,
+            self.paint(RESET),
+            self.paint(BOLD),
+            diagnostic.message,
+            self.paint(RESET)
+        );
+
+        let location = format!("{}:{}", span.line(), span.column());
+        output += &format!(
+            "{}{}-->{} {}",
+            gutter,
+            self.paint(BLUE),
+            self.paint(RESET),
+            match self.path {
+                Some(path) => format!("{}:{}", path, location),
+                None => location,
+            }
+        );
+
+        // A span which does not fit the source, such as one from another
+        // source, is reported without the offending line
+        if let Some((line_text, indent, underline)) = self.snippet(span) {
+            output += &format!("\n{} {}|{}\n", gutter, self.paint(BLUE), self.paint(RESET));
+            output += &format!(
+                "{}{} |{} {}\n",
+                self.paint(BLUE),
+                line_number,
+                self.paint(RESET),
+                line_text
+            );
+            output += &format!(
+                "{} {}|{} {}{}{}{}",
+                gutter,
+                self.paint(BLUE),
+                self.paint(RESET),
+                indent,
+                self.paint(RED),
+                underline,
+                self.paint(RESET)
+            );
+        }
+
+        if let Some(help) = diagnostic.help {
+            output += &format!(
+                "\n{} {}={} {}help{}: {}",
+                gutter,
+                self.paint(BLUE),
+                self.paint(RESET),
+                self.paint(CYAN),
+                self.paint(RESET),
+                help
+            );
+        }
+
+        if !diagnostic.trace.is_empty() {
+            output += &format!(
+                "\n{} {}={} {}stack trace{}:",
+                gutter,
+                self.paint(BLUE),
+                self.paint(RESET),
+                self.paint(CYAN),
+                self.paint(RESET)
+            );
+
+            for frame in &diagnostic.trace {
+                output += &format!("\n{}     {}", gutter, frame);
+            }
+        }
+
+        output
+    }
+
+    /// The line of source containing the span, along with the indent and
+    /// underline pointing at the span's characters on that line. Nothing is
+    /// returned if the span lies outside the source or splits a character.
+    fn snippet(&self, span: Span) -> Option<(&'a str, String, String)> {
+        let (line_start, line_text) = self.line_containing(span.start())?;
+
+        // Point at the characters of the span on its first line, or at the
+        // position itself for empty spans such as the end of the file
+        let indent = self.source[line_start..span.start()]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        let span_end = span.end().clamp(span.start(), line_start + line_text.len());
+        let underline = "^".repeat(
+            self.source
+                .get(span.start()..span_end)?
+                .chars()
+                .count()
+                .max(1),
+        );
+
+        Some((line_text, indent, underline))
+    }
+
+    /// Finds the line of source containing the given byte offset, returning
+    /// the offset where that line starts along with its text
+    fn line_containing(&self, offset: usize) -> Option<(usize, &'a str)> {
+        let start = self.source.get(..offset)?.rfind('\n').map_or(0, |i| i + 1);
+        let end = self.source[offset..]
+            .find('\n')
+            .map_or(self.source.len(), |i| offset + i);
+
+        Some((start, self.source[start..end].trim_end_matches('\r')))
+    }
+
+    fn paint(&self, code: &'static str) -> &'static str {
+        if self.colored {
+            code
+        } else {
+            ""
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render_with_help() {
+        let source = "var a = 1;\nprint a + \"b\";\n";
+        let diagnostic = Diagnostic::new(
+            "E302",
+            "Operands must be two numbers or two strings.".to_string(),
+            Span::new(19, 20, 2, 9),
+        )
+        .with_help(Some("convert one side first"));
+
+        assert_eq!(
+            Renderer::new(source, Some("script.lox"), false).render(&diagnostic),
+            "error[E302]: Operands must be two numbers or two strings.
+ --> script.lox:2:9
+  |
+2 | print a + \"b\";
+  |         ^
+  = help: convert one side first"
+        );
+    }
+
+    #[test]
+    fn test_render_with_trace() {
+        let source = "fun f() {\n  return -\"a\";\n}\nf();\n";
+        let diagnostic = Diagnostic::new(
+            "E300",
+            "Operand must be a number.".to_string(),
+            Span::new(19, 20, 2, 10),
+        )
+        .with_trace(vec![
+            "[line 2] in f()".to_string(),
+            "[line 4] in script".to_string(),
+        ]);
+
+        assert_eq!(
+            Renderer::new(source, None, false).render(&diagnostic),
+            "error[E300]: Operand must be a number.
+ --> 2:10
+  |
+2 |   return -\"a\";
+  |          ^
+  = stack trace:
+      [line 2] in f()
+      [line 4] in script"
+        );
+    }
+
+    #[test]
+    fn test_render_multi_character_and_empty_spans() {
+        let source = "\tfoo(größe)";
+        let diagnostic = Diagnostic::new("E000", "Message".to_string(), Span::new(5, 12, 1, 6));
+
+        assert_eq!(
+            Renderer::new(source, None, false).render(&diagnostic),
+            "error[E000]: Message\n --> 1:6\n  |\n1 | \tfoo(größe)\n  | \t    ^^^^^"
+        );
+
+        let diagnostic = Diagnostic::new("E000", "Message".to_string(), Span::new(13, 13, 1, 12));
+        assert!(Renderer::new(source, None, false)
+            .render(&diagnostic)
+            .ends_with("  | \t          ^"));
+    }
+
+    #[test]
+    fn test_render_span_outside_source() {
+        let diagnostic = Diagnostic::new(
+            "E300",
+            "Operand must be a number.".to_string(),
+            Span::new(40, 47, 1, 41),
+        )
+        .with_help(Some("convert it first"));
+
+        assert_eq!(
+            Renderer::new("f();\n", None, false).render(&diagnostic),
+            "error[E300]: Operand must be a number.\n --> 1:41\n  = help: convert it first"
+        );
+
+        // Offsets inside a character of the source
+        let diagnostic = Diagnostic::new("E000", "Message".to_string(), Span::new(3, 4, 1, 3));
+        assert_eq!(
+            Renderer::new("größe", None, false).render(&diagnostic),
+            "error[E000]: Message\n --> 1:3"
+        );
+    }
+
+    #[test]
+    fn test_render_colored() {
+        let diagnostic = Diagnostic::new("E000", "Message".to_string(), Span::new(0, 1, 1, 1));
+        let output = Renderer::new("x", None, true).render(&diagnostic);
+
+        assert!(output.starts_with("\x1b[1;31merror[E000]\x1b[0m"));
+    }
+}
@@ -0,0 +1,9 @@
+pub mod class;
+pub mod collector;
+pub mod environment;
+pub mod error;
+pub mod function;
+pub mod host;
+pub mod interpreter;
+pub mod native;
+pub mod value;
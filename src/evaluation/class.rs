@@ -0,0 +1,109 @@
+use crate::evaluation::error::{RuntimeError, RuntimeErrorType};
+use crate::evaluation::function::LoxFunction;
+use crate::evaluation::value::Value;
+use crate::lexical_analysis::symbol::Symbol;
+use crate::lexical_analysis::token::Token;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// A class value. Calling it creates a new instance.
+#[derive(Debug)]
+pub struct LoxClass {
+    pub name: String,
+    pub superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<Symbol, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<Symbol, Rc<LoxFunction>>,
+    ) -> Self {
+        Self {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    /// Looks a method up on this class, then along the superclass chain
+    pub fn find_method(&self, name: Symbol) -> Option<Rc<LoxFunction>> {
+        match self.methods.get(&name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self.superclass.as_ref()?.find_method(name),
+        }
+    }
+
+    /// The class's own methods, not including inherited ones
+    pub fn methods(&self) -> impl Iterator<Item = &Rc<LoxFunction>> {
+        self.methods.values()
+    }
+
+    /// A class takes the same arguments as its initializer, if it has one
+    pub fn arity(&self) -> usize {
+        self.find_method(Symbol::INIT)
+            .map_or(0, |init| init.arity())
+    }
+}
+
+impl std::fmt::Display for LoxClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// An instance of a class, holding its own fields
+#[derive(Debug)]
+pub struct LoxInstance {
+    pub class: Rc<LoxClass>,
+    fields: HashMap<Symbol, Value>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Reads a property. Fields shadow methods, and methods are bound to the
+    /// instance so that `this` keeps referring to it.
+    pub fn get(instance: &Rc<RefCell<LoxInstance>>, name: &Token) -> Result<Value, RuntimeError> {
+        if let Some(value) = instance.borrow().fields.get(&name.symbol()) {
+            return Ok(value.clone());
+        }
+
+        let method = instance.borrow().class.find_method(name.symbol());
+        match method {
+            Some(method) => Ok(Value::Function(Rc::new(
+                method.bind(Value::Instance(Rc::clone(instance))),
+            ))),
+            None => Err(RuntimeError::new(
+                RuntimeErrorType::UndefinedProperty(name.lexeme.to_string()),
+                name.span(),
+            )),
+        }
+    }
+
+    pub fn set(&mut self, name: &Token, value: Value) {
+        self.fields.insert(name.symbol(), value);
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = &Value> {
+        self.fields.values()
+    }
+
+    /// Removes every field of the instance
+    pub fn take_fields(&mut self) -> HashMap<Symbol, Value> {
+        std::mem::take(&mut self.fields)
+    }
+}
+
+impl std::fmt::Display for LoxInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
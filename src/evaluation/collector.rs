@@ -0,0 +1,289 @@
+use crate::evaluation::class::{LoxClass, LoxInstance};
+use crate::evaluation::environment::Environment;
+use crate::evaluation::function::LoxFunction;
+use crate::evaluation::value::Value;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::{Rc, Weak};
+
+/// How many objects may be tracked before the first collection
+const INITIAL_THRESHOLD: usize = 10_000;
+
+/// How much the number of tracked objects may grow, relative to what
+/// survived the last collection, before the next one
+const GROWTH_FACTOR: usize = 2;
+
+/// A tracked object, without keeping it alive
+#[derive(Debug)]
+pub enum Tracked {
+    Environment(Weak<RefCell<Environment>>),
+    Function(Weak<LoxFunction>),
+    Class(Weak<LoxClass>),
+    Instance(Weak<RefCell<LoxInstance>>),
+}
+
+impl From<&Rc<RefCell<Environment>>> for Tracked {
+    fn from(environment: &Rc<RefCell<Environment>>) -> Self {
+        Tracked::Environment(Rc::downgrade(environment))
+    }
+}
+
+impl From<&Rc<LoxFunction>> for Tracked {
+    fn from(function: &Rc<LoxFunction>) -> Self {
+        Tracked::Function(Rc::downgrade(function))
+    }
+}
+
+impl From<&Rc<LoxClass>> for Tracked {
+    fn from(class: &Rc<LoxClass>) -> Self {
+        Tracked::Class(Rc::downgrade(class))
+    }
+}
+
+impl From<&Rc<RefCell<LoxInstance>>> for Tracked {
+    fn from(instance: &Rc<RefCell<LoxInstance>>) -> Self {
+        Tracked::Instance(Rc::downgrade(instance))
+    }
+}
+
+impl Tracked {
+    fn upgrade(&self) -> Option<Object> {
+        Some(match self {
+            Tracked::Environment(environment) => Object::Environment(environment.upgrade()?),
+            Tracked::Function(function) => Object::Function(function.upgrade()?),
+            Tracked::Class(class) => Object::Class(class.upgrade()?),
+            Tracked::Instance(instance) => Object::Instance(instance.upgrade()?),
+        })
+    }
+}
+
+/// A tracked object, kept alive for the duration of a collection
+enum Object {
+    Environment(Rc<RefCell<Environment>>),
+    Function(Rc<LoxFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+}
+
+impl Object {
+    fn address(&self) -> usize {
+        match self {
+            Object::Environment(environment) => address(environment),
+            Object::Function(function) => address(function),
+            Object::Class(class) => address(class),
+            Object::Instance(instance) => address(instance),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Object::Environment(environment) => Rc::strong_count(environment),
+            Object::Function(function) => Rc::strong_count(function),
+            Object::Class(class) => Rc::strong_count(class),
+            Object::Instance(instance) => Rc::strong_count(instance),
+        }
+    }
+
+    /// The addresses of the objects this one holds a reference to, once per
+    /// reference, or `None` if it is borrowed mutably and cannot be looked at
+    fn references(&self) -> Option<Vec<usize>> {
+        let mut references = Vec::new();
+
+        match self {
+            Object::Environment(environment) => {
+                let environment = environment.try_borrow().ok()?;
+                references.extend(environment.values().filter_map(value_address));
+                references.extend(environment.enclosing().map(address));
+            }
+            Object::Function(function) => references.push(address(&function.closure)),
+            Object::Class(class) => {
+                references.extend(class.superclass.iter().map(address));
+                references.extend(class.methods().map(address));
+            }
+            Object::Instance(instance) => {
+                let instance = instance.try_borrow().ok()?;
+                references.push(address(&instance.class));
+                references.extend(instance.fields().filter_map(value_address));
+            }
+        }
+
+        Some(references)
+    }
+
+    /// Drops the references the object holds to values, which breaks every
+    /// cycle running through it
+    fn clear(&self) {
+        // The values are dropped after the borrow ends, as dropping them can
+        // free other objects
+        let _values = match self {
+            Object::Environment(environment) => match environment.try_borrow_mut() {
+                Ok(mut environment) => environment.take_values(),
+                Err(_) => return,
+            },
+            Object::Instance(instance) => match instance.try_borrow_mut() {
+                Ok(mut instance) => instance.take_fields(),
+                Err(_) => return,
+            },
+            // Functions and classes cannot be changed after they are created,
+            // so they only refer to older objects and any cycle they are part
+            // of also runs through an environment or an instance
+            Object::Function(_) | Object::Class(_) => return,
+        };
+    }
+
+    fn downgrade(&self) -> Tracked {
+        match self {
+            Object::Environment(environment) => Tracked::from(environment),
+            Object::Function(function) => Tracked::from(function),
+            Object::Class(class) => Tracked::from(class),
+            Object::Instance(instance) => Tracked::from(instance),
+        }
+    }
+}
+
+/// Identifies an object, however many references to it there are
+fn address<T>(object: &Rc<T>) -> usize {
+    Rc::as_ptr(object) as *const () as usize
+}
+
+/// The address of the tracked object a value refers to, if any
+fn value_address(value: &Value) -> Option<usize> {
+    match value {
+        Value::Function(function) => Some(address(function)),
+        Value::Class(class) => Some(address(class)),
+        Value::Instance(instance) => Some(address(instance)),
+        _ => None,
+    }
+}
+
+/// Frees the reference cycles the tree walker's values form, such as a
+/// function stored in the environment it closes over, or an instance with a
+/// field holding itself. Reference counting frees everything else, and
+/// strings can never be part of a cycle.
+///
+/// The interpreter keeps values in Rust variables while it works, which the
+/// collector cannot see, and registering each of them as a root would be
+/// needed on every step. Instead, roots are found by trial deletion: they are
+/// the objects which have more references than the tracked objects account
+/// for, as the rest must come from outside: the globals, the environments of
+/// running calls, any value in flight, and values the host holds. Everything
+/// reachable from a root is marked, and the rest is unreachable garbage whose
+/// cycles are broken so that it is freed.
+#[derive(Debug)]
+pub struct Collector {
+    /// Every object which can be part of a cycle. An object may be tracked
+    /// more than once until the next collection.
+    tracked: Vec<Tracked>,
+    /// How many objects may be tracked before the next collection.
+    next_gc: usize,
+    /// Whether to collect whenever an object is tracked.
+    stress: bool,
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Self {
+            tracked: Vec::new(),
+            next_gc: INITIAL_THRESHOLD,
+            stress: false,
+        }
+    }
+}
+
+impl Collector {
+    pub fn set_stress(&mut self, stress: bool) {
+        self.stress = stress;
+    }
+
+    /// Starts tracking a newly allocated object, collecting first if enough
+    /// objects have been allocated since the last collection
+    pub fn track(&mut self, object: impl Into<Tracked>) {
+        if self.stress || self.tracked.len() >= self.next_gc {
+            self.collect();
+        }
+
+        self.tracked.push(object.into());
+    }
+
+    /// How many tracked objects are alive
+    #[allow(unused)]
+    pub(crate) fn len(&self) -> usize {
+        self.tracked
+            .iter()
+            .filter_map(Tracked::upgrade)
+            .map(|object| object.address())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Frees every tracked object which cannot be reached from the roots
+    pub fn collect(&mut self) {
+        // Objects freed by reference counting are dropped from the list here
+        let mut index = HashMap::new();
+        let mut objects = Vec::new();
+        for object in self
+            .tracked
+            .drain(..)
+            .filter_map(|tracked| tracked.upgrade())
+        {
+            index.entry(object.address()).or_insert_with(|| {
+                objects.push(object);
+                objects.len() - 1
+            });
+        }
+
+        // Count the references each object gets from other tracked objects
+        let mut internal = vec![0; objects.len()];
+        let references = objects
+            .iter()
+            .map(|object| {
+                let references = object
+                    .references()?
+                    .into_iter()
+                    .filter_map(|address| index.get(&address).copied())
+                    .collect::<Vec<_>>();
+
+                for &reference in &references {
+                    internal[reference] += 1;
+                }
+
+                Some(references)
+            })
+            .collect::<Vec<_>>();
+
+        // The collector itself holds one reference to each object. An object
+        // which cannot be looked at is kept, along with what it refers to, as
+        // those references were not counted.
+        let mut marked = vec![false; objects.len()];
+        let mut gray = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            if object.strong_count() - 1 > internal[i] || references[i].is_none() {
+                marked[i] = true;
+                gray.push(i);
+            }
+        }
+
+        while let Some(i) = gray.pop() {
+            for &reference in references[i].iter().flatten() {
+                if !marked[reference] {
+                    marked[reference] = true;
+                    gray.push(reference);
+                }
+            }
+        }
+
+        for (object, &marked) in objects.iter().zip(&marked) {
+            if !marked {
+                object.clear();
+            }
+        }
+
+        self.tracked = objects
+            .iter()
+            .zip(&marked)
+            .filter(|(_, &marked)| marked)
+            .map(|(object, _)| object.downgrade())
+            .collect();
+        self.next_gc = (self.tracked.len() * GROWTH_FACTOR).max(INITIAL_THRESHOLD);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::evaluation::error::{RuntimeError, RuntimeErrorType};
+use crate::evaluation::value::Value;
+use crate::lexical_analysis::symbol::Symbol;
+use crate::lexical_analysis::token::Token;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// A scope mapping variable names to their values. Each block gets its own
+/// environment which points at the environment of the surrounding code.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<Symbol, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// Declares a variable in this scope, shadowing or redefining any previous one
+    pub fn define(&mut self, name: Symbol, value: Value) {
+        self.values.insert(name, value);
+    }
+
+    /// The values of the variables declared in this scope
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.values.values()
+    }
+
+    pub fn enclosing(&self) -> Option<&Rc<RefCell<Environment>>> {
+        self.enclosing.as_ref()
+    }
+
+    /// Removes every variable declared in this scope
+    pub fn take_values(&mut self) -> HashMap<Symbol, Value> {
+        std::mem::take(&mut self.values)
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        self.lookup(name.symbol()).ok_or_else(|| {
+            RuntimeError::new(
+                RuntimeErrorType::UndefinedVariable(name.lexeme.to_string()),
+                name.span(),
+            )
+        })
+    }
+
+    /// Finds a variable by name in this scope or any enclosing one
+    pub fn lookup(&self, name: Symbol) -> Option<Value> {
+        match self.values.get(&name) {
+            Some(value) => Some(value.clone()),
+            None => self.enclosing.as_ref()?.borrow().lookup(name),
+        }
+    }
+
+    /// Assigns to an existing variable in the nearest scope declaring it
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        if let Some(slot) = self.values.get_mut(&name.symbol()) {
+            *slot = value;
+            return Ok(());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(RuntimeError::new(
+                RuntimeErrorType::UndefinedVariable(name.lexeme.to_string()),
+                name.span(),
+            )),
+        }
+    }
+
+    /// Reads a variable from the scope `depth` levels out from this one, where
+    /// the resolver found it to be declared
+    pub fn get_at(environment: &Rc<RefCell<Environment>>, depth: usize, name: Symbol) -> Value {
+        Self::ancestor(environment, depth)
+            .borrow()
+            .values
+            .get(&name)
+            .cloned()
+            .expect("resolved variables are always declared")
+    }
+
+    /// Assigns to a variable in the scope `depth` levels out from this one
+    pub fn assign_at(
+        environment: &Rc<RefCell<Environment>>,
+        depth: usize,
+        name: Symbol,
+        value: Value,
+    ) {
+        Self::ancestor(environment, depth)
+            .borrow_mut()
+            .values
+            .insert(name, value);
+    }
+
+    fn ancestor(environment: &Rc<RefCell<Environment>>, depth: usize) -> Rc<RefCell<Environment>> {
+        let mut environment = Rc::clone(environment);
+
+        for _ in 0..depth {
+            let enclosing = environment
+                .borrow()
+                .enclosing
+                .clone()
+                .expect("resolved scopes are always nested this deeply");
+            environment = enclosing;
+        }
+
+        environment
+    }
+}
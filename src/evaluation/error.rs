@@ -0,0 +1,271 @@
+use crate::diagnostics::Diagnostic;
+use crate::evaluation::value::Value;
+use crate::lexical_analysis::span::Span;
+
+/// How many calls of a stack trace are shown at most
+const MAX_TRACE_LINES: usize = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    typ: RuntimeErrorType,
+    span: Span,
+    /// The calls which were active when the error happened, innermost first,
+    /// ending with the top level of the program.
+    trace: Vec<TraceFrame>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeErrorType {
+    OperandMustBeNumber,
+    OperandsMustBeNumbers,
+    OperandsMustBeNumbersOrStrings,
+    UndefinedVariable(String),
+    NotCallable,
+    ArityMismatch {
+        expected: usize,
+        got: usize,
+    },
+    StackOverflow,
+    OnlyInstancesHaveProperties,
+    OnlyInstancesHaveFields,
+    UndefinedProperty(String),
+    SuperclassMustBeClass,
+    WrongArgumentType {
+        /// The position of the argument, starting at 1.
+        position: usize,
+        expected: &'static str,
+        got: &'static str,
+    },
+    InvalidExitCode(f64),
+    /// Not a failure: the program called `exit` with the given code, and
+    /// unwinds to whatever is running it.
+    Exit(i32),
+    /// A function of the host failed, with the given message.
+    Host(String),
+    NotConstructible(String),
+    ReadOnlyProperty(String),
+    /// A host object was used again while one of its methods was running.
+    ObjectInUse(String),
+    /// A function of the program called by the host failed. The error keeps
+    /// the span and trace it had in the program.
+    Callback(Box<RuntimeError>),
+    /// `print` could not write to the output.
+    Output(std::io::ErrorKind),
+}
+
+impl RuntimeError {
+    pub fn new(typ: RuntimeErrorType, span: Span) -> Self {
+        Self {
+            typ,
+            span,
+            trace: Vec::new(),
+        }
+    }
+
+    /// The error of a call to a host function or class. Errors of functions
+    /// of the program which the host called come through unchanged.
+    pub fn from_host(typ: RuntimeErrorType, span: Span) -> Self {
+        match typ {
+            RuntimeErrorType::Callback(error) => *error,
+            typ => RuntimeError::new(typ, span),
+        }
+    }
+
+    pub fn with_trace(mut self, trace: Vec<TraceFrame>) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    pub fn typ(&self) -> &RuntimeErrorType {
+        &self.typ
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// The code the program asked to exit with, if this is not a failure
+    /// but a call to `exit`
+    pub fn exit_code(&self) -> Option<i32> {
+        match self.typ {
+            RuntimeErrorType::Exit(code) => Some(code),
+            _ => None,
+        }
+    }
+
+    pub fn trace(&self) -> &[TraceFrame] {
+        &self.trace
+    }
+
+    /// The trace as shown to the user. Runaway recursion would bury the
+    /// error under hundreds of identical lines, so long traces are cut short.
+    fn trace_lines(&self) -> Vec<String> {
+        let mut lines = self
+            .trace
+            .iter()
+            .take(MAX_TRACE_LINES)
+            .map(TraceFrame::to_string)
+            .collect::<Vec<_>>();
+
+        if self.trace.len() > MAX_TRACE_LINES {
+            lines.push(format!(
+                "... and {} more calls",
+                self.trace.len() - MAX_TRACE_LINES
+            ));
+        }
+
+        lines
+    }
+}
+
+/// A call which was active when a runtime error happened
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    /// The name of the function, or `None` for the top level of the program.
+    function: Option<String>,
+    /// The line the call was executing.
+    line: usize,
+}
+
+impl TraceFrame {
+    pub fn new(function: Option<String>, line: usize) -> Self {
+        Self { function, line }
+    }
+}
+
+impl std::fmt::Display for TraceFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.function {
+            Some(function) => write!(f, "[line {}] in {}()", self.line, function),
+            None => write!(f, "[line {}] in script", self.line),
+        }
+    }
+}
+
+impl RuntimeErrorType {
+    pub fn code(&self) -> &'static str {
+        match self {
+            RuntimeErrorType::OperandMustBeNumber => "E300",
+            RuntimeErrorType::OperandsMustBeNumbers => "E301",
+            RuntimeErrorType::OperandsMustBeNumbersOrStrings => "E302",
+            RuntimeErrorType::UndefinedVariable(_) => "E303",
+            RuntimeErrorType::NotCallable => "E304",
+            RuntimeErrorType::ArityMismatch { .. } => "E305",
+            RuntimeErrorType::StackOverflow => "E306",
+            RuntimeErrorType::OnlyInstancesHaveProperties => "E307",
+            RuntimeErrorType::OnlyInstancesHaveFields => "E308",
+            RuntimeErrorType::UndefinedProperty(_) => "E309",
+            RuntimeErrorType::SuperclassMustBeClass => "E310",
+            RuntimeErrorType::WrongArgumentType { .. } => "E311",
+            RuntimeErrorType::InvalidExitCode(_) => "E312",
+            RuntimeErrorType::Exit(_) => "E313",
+            RuntimeErrorType::Host(_) => "E314",
+            RuntimeErrorType::NotConstructible(_) => "E315",
+            RuntimeErrorType::ReadOnlyProperty(_) => "E316",
+            RuntimeErrorType::ObjectInUse(_) => "E317",
+            RuntimeErrorType::Callback(error) => error.typ.code(),
+            RuntimeErrorType::Output(_) => "E318",
+        }
+    }
+
+    pub fn help(&self) -> Option<&'static str> {
+        match self {
+            RuntimeErrorType::UndefinedVariable(_) => {
+                Some("variables must be declared with 'var' before they are used")
+            }
+            RuntimeErrorType::StackOverflow => {
+                Some("check that every recursive function has a base case")
+            }
+            RuntimeErrorType::WrongArgumentType { .. } => {
+                Some("convert the argument first with 'str' or 'num'")
+            }
+            RuntimeErrorType::NotConstructible(_) => {
+                Some("instances of this class can only come from the host")
+            }
+            RuntimeErrorType::Callback(error) => error.typ.help(),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for RuntimeErrorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeErrorType::OperandMustBeNumber => write!(f, "Operand must be a number."),
+            RuntimeErrorType::OperandsMustBeNumbers => write!(f, "Operands must be numbers."),
+            RuntimeErrorType::OperandsMustBeNumbersOrStrings => {
+                write!(f, "Operands must be two numbers or two strings.")
+            }
+            RuntimeErrorType::UndefinedVariable(name) => {
+                write!(f, "Undefined variable '{}'.", name)
+            }
+            RuntimeErrorType::NotCallable => write!(f, "Can only call functions and classes."),
+            RuntimeErrorType::ArityMismatch { expected, got } => {
+                write!(f, "Expected {} arguments but got {}.", expected, got)
+            }
+            RuntimeErrorType::StackOverflow => write!(f, "Stack overflow."),
+            RuntimeErrorType::OnlyInstancesHaveProperties => {
+                write!(f, "Only instances have properties.")
+            }
+            RuntimeErrorType::OnlyInstancesHaveFields => write!(f, "Only instances have fields."),
+            RuntimeErrorType::UndefinedProperty(name) => {
+                write!(f, "Undefined property '{}'.", name)
+            }
+            RuntimeErrorType::SuperclassMustBeClass => write!(f, "Superclass must be a class."),
+            RuntimeErrorType::WrongArgumentType {
+                position,
+                expected,
+                got,
+            } => write!(
+                f,
+                "Expected a {} as argument {} but got a {}.",
+                expected, position, got
+            ),
+            RuntimeErrorType::InvalidExitCode(code) => {
+                write!(f, "Exit code must be a whole number, got {}.", code)
+            }
+            RuntimeErrorType::Exit(code) => write!(f, "Exited with code {}.", code),
+            RuntimeErrorType::Host(message) => write!(f, "{}", message),
+            RuntimeErrorType::NotConstructible(class) => {
+                write!(f, "Can't create instances of '{}'.", class)
+            }
+            RuntimeErrorType::ReadOnlyProperty(name) => {
+                write!(f, "Can't assign to read-only property '{}'.", name)
+            }
+            RuntimeErrorType::ObjectInUse(class) => {
+                write!(f, "This '{}' object is already in use.", class)
+            }
+            RuntimeErrorType::Callback(error) => write!(f, "{}", error.typ),
+            RuntimeErrorType::Output(kind) => write!(f, "Could not write output: {}.", kind),
+        }
+    }
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\n[line {}]", self.typ, self.span.line())
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(error: &RuntimeError) -> Self {
+        Diagnostic::new(error.typ.code(), error.typ.to_string(), error.span())
+            .with_help(error.typ.help())
+            .with_trace(error.trace_lines())
+    }
+}
+
+/// The reasons execution of statements can stop before reaching their end
+#[derive(Debug)]
+pub enum Unwind {
+    /// A runtime error, propagating up to the top level.
+    Error(RuntimeError),
+    /// A `return` statement, handing a value back to the nearest function call.
+    Return(Value),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
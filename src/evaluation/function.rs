@@ -0,0 +1,61 @@
+use crate::evaluation::environment::Environment;
+use crate::evaluation::value::Value;
+use crate::lexical_analysis::symbol::Symbol;
+use crate::syntax_analysis::expression::ExprId;
+use crate::syntax_analysis::statement::Function;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// A function value: a declaration together with the environment it was
+/// declared in, so that it can keep using the variables around it
+#[derive(Debug)]
+pub struct LoxFunction {
+    pub declaration: Rc<Function>,
+    pub closure: Rc<RefCell<Environment>>,
+    /// The scope depths the resolver found for the program the function was
+    /// declared in.
+    pub locals: Rc<HashMap<ExprId, usize>>,
+    /// Whether this is a class's `init` method, which always returns `this`.
+    pub is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        declaration: Rc<Function>,
+        closure: Rc<RefCell<Environment>>,
+        locals: Rc<HashMap<ExprId, usize>>,
+        is_initializer: bool,
+    ) -> Self {
+        Self {
+            declaration,
+            closure,
+            locals,
+            is_initializer,
+        }
+    }
+
+    /// Creates a copy of this method whose closure defines `this` as the
+    /// given instance
+    pub fn bind(&self, instance: Value) -> LoxFunction {
+        let mut environment = Environment::new(Rc::clone(&self.closure));
+        environment.define(Symbol::THIS, instance);
+
+        LoxFunction::new(
+            Rc::clone(&self.declaration),
+            Rc::new(RefCell::new(environment)),
+            Rc::clone(&self.locals),
+            self.is_initializer,
+        )
+    }
+
+    pub fn arity(&self) -> usize {
+        self.declaration.arguments.len()
+    }
+}
+
+impl std::fmt::Display for LoxFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
@@ -0,0 +1,329 @@
+use crate::evaluation::error::RuntimeErrorType;
+use crate::evaluation::interpreter::Interpreter;
+use crate::evaluation::value::Value;
+use crate::lexical_analysis::symbol::Symbol;
+use crate::lexical_analysis::token::Token;
+use std::any::{Any, TypeId};
+use std::cell::{RefCell, RefMut};
+use std::collections::HashMap;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+/// What host functions give back: a value, or the error to report at the
+/// call in the program
+pub type HostResult<T = Value> = Result<T, RuntimeErrorType>;
+
+/// The signature of Rust closures callable from programs
+type Callback = dyn Fn(&mut Ctx, &[Value]) -> HostResult;
+
+/// The signature of a method, given the instance's object
+type Method = dyn Fn(&mut Ctx, &dyn Any, &[Value]) -> HostResult;
+
+/// The signature of a constructor, creating the object of a new instance
+type Constructor = dyn Fn(&mut Ctx, &[Value]) -> HostResult<Rc<dyn Any>>;
+
+/// The signature of a property getter, given the instance's object
+type Getter = dyn Fn(&dyn Any) -> HostResult;
+
+/// The signature of a property setter, given the instance's object
+type Setter = dyn Fn(&dyn Any, Value) -> HostResult<()>;
+
+/// The access host functions have to the interpreter calling them
+pub struct Ctx<'a> {
+    interpreter: &'a mut Interpreter,
+    /// The closing parenthesis of the call in the program, where errors of
+    /// calls made through the context are reported.
+    parenthesis: &'a Token<'static>,
+}
+
+impl<'a> Ctx<'a> {
+    pub(crate) fn new(interpreter: &'a mut Interpreter, parenthesis: &'a Token<'static>) -> Self {
+        Self {
+            interpreter,
+            parenthesis,
+        }
+    }
+
+    /// Calls a function or class of the program, such as a callback it
+    /// handed to the host. Its errors are reported where they happened in
+    /// the program, not at the call to the host.
+    pub fn call(&mut self, callee: &Value, arguments: Vec<Value>) -> HostResult {
+        self.interpreter
+            .call(callee.clone(), arguments, self.parenthesis)
+            .map_err(|error| RuntimeErrorType::Callback(Box::new(error)))
+    }
+
+    /// Where the program's `print` statements write to
+    pub fn output(&mut self) -> &mut dyn Write {
+        self.interpreter.output()
+    }
+}
+
+/// A Rust closure which programs can call like any other function
+pub struct HostFunction {
+    pub name: String,
+    pub arity: usize,
+    function: Box<Callback>,
+}
+
+impl HostFunction {
+    pub fn new(
+        name: &str,
+        arity: usize,
+        function: impl Fn(&mut Ctx, &[Value]) -> HostResult + 'static,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+
+    /// Calls the closure. The caller has already checked the number of
+    /// arguments.
+    pub(crate) fn call(&self, ctx: &mut Ctx, arguments: &[Value]) -> HostResult {
+        (self.function)(ctx, arguments)
+    }
+}
+
+impl std::fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HostFunction({})", self.name)
+    }
+}
+
+impl std::fmt::Display for HostFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+/// A Rust type exposed to programs as a class. Its instances wrap a shared
+/// `Rc<RefCell<T>>`, so the host sees what programs do to them.
+pub struct HostClass {
+    pub name: String,
+    /// The type of the objects wrapped by instances.
+    type_id: TypeId,
+    /// The arity and function of the constructor, if programs may create
+    /// instances themselves.
+    constructor: Option<(usize, Box<Constructor>)>,
+    methods: HashMap<Symbol, (usize, Rc<Method>)>,
+    getters: HashMap<Symbol, Box<Getter>>,
+    setters: HashMap<Symbol, Box<Setter>>,
+}
+
+impl HostClass {
+    /// Starts describing the class programs see for the type `T`
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<T: 'static>(name: &str) -> HostClassBuilder<T> {
+        HostClassBuilder {
+            class: HostClass {
+                name: name.to_string(),
+                type_id: TypeId::of::<RefCell<T>>(),
+                constructor: None,
+                methods: HashMap::new(),
+                getters: HashMap::new(),
+                setters: HashMap::new(),
+            },
+            typ: PhantomData,
+        }
+    }
+
+    /// Wraps an object of the host as an instance of this class
+    ///
+    /// # Panics
+    ///
+    /// If the object is not of the type the class was built for.
+    pub fn instance<T: 'static>(self: &Rc<Self>, object: Rc<RefCell<T>>) -> Value {
+        assert_eq!(
+            self.type_id,
+            TypeId::of::<RefCell<T>>(),
+            "not an object of the type of class '{}'",
+            self.name
+        );
+
+        Value::HostInstance(Rc::new(HostInstance {
+            class: Rc::clone(self),
+            object,
+        }))
+    }
+
+    /// A class takes the same arguments as its constructor, if it has one
+    pub fn arity(&self) -> usize {
+        self.constructor.as_ref().map_or(0, |(arity, _)| *arity)
+    }
+
+    /// Creates an instance on behalf of a program
+    pub(crate) fn construct(self: &Rc<Self>, ctx: &mut Ctx, arguments: &[Value]) -> HostResult {
+        let Some((_, constructor)) = &self.constructor else {
+            return Err(RuntimeErrorType::NotConstructible(self.name.clone()));
+        };
+
+        Ok(Value::HostInstance(Rc::new(HostInstance {
+            class: Rc::clone(self),
+            object: constructor(ctx, arguments)?,
+        })))
+    }
+}
+
+impl std::fmt::Debug for HostClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HostClass({})", self.name)
+    }
+}
+
+impl std::fmt::Display for HostClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Describes the constructor, methods and properties of a `HostClass` in
+/// terms of the type `T` it exposes
+pub struct HostClassBuilder<T> {
+    class: HostClass,
+    typ: PhantomData<T>,
+}
+
+impl<T: 'static> HostClassBuilder<T> {
+    /// Lets programs create instances by calling the class
+    pub fn constructor(
+        mut self,
+        arity: usize,
+        constructor: impl Fn(&mut Ctx, &[Value]) -> HostResult<T> + 'static,
+    ) -> Self {
+        let function = move |ctx: &mut Ctx, arguments: &[Value]| {
+            let object: Rc<dyn Any> = Rc::new(RefCell::new(constructor(ctx, arguments)?));
+            Ok(object)
+        };
+
+        self.class.constructor = Some((arity, Box::new(function)));
+        self
+    }
+
+    pub fn method(
+        mut self,
+        name: &str,
+        arity: usize,
+        method: impl Fn(&mut T, &mut Ctx, &[Value]) -> HostResult + 'static,
+    ) -> Self {
+        let class = self.class.name.clone();
+        let function = move |ctx: &mut Ctx, object: &dyn Any, arguments: &[Value]| {
+            method(&mut *borrow_mut::<T>(object, &class)?, ctx, arguments)
+        };
+
+        self.class
+            .methods
+            .insert(Symbol::intern(name), (arity, Rc::new(function)));
+        self
+    }
+
+    /// Adds a property programs can read
+    pub fn getter(mut self, name: &str, getter: impl Fn(&T) -> Value + 'static) -> Self {
+        let class = self.class.name.clone();
+        let function = move |object: &dyn Any| Ok(getter(&*borrow_mut::<T>(object, &class)?));
+
+        self.class
+            .getters
+            .insert(Symbol::intern(name), Box::new(function));
+        self
+    }
+
+    /// Lets programs assign to a property, which may reject values by
+    /// returning an error
+    pub fn setter(
+        mut self,
+        name: &str,
+        setter: impl Fn(&mut T, Value) -> HostResult<()> + 'static,
+    ) -> Self {
+        let class = self.class.name.clone();
+        let function = move |object: &dyn Any, value: Value| {
+            setter(&mut *borrow_mut::<T>(object, &class)?, value)
+        };
+
+        self.class
+            .setters
+            .insert(Symbol::intern(name), Box::new(function));
+        self
+    }
+
+    pub fn build(self) -> HostClass {
+        self.class
+    }
+}
+
+/// Gets at the object of an instance. An object is borrowed for as long as
+/// one of its methods runs, so a method calling back into the program can
+/// not have the same object used again.
+fn borrow_mut<'a, T: 'static>(object: &'a dyn Any, class: &str) -> HostResult<RefMut<'a, T>> {
+    object
+        .downcast_ref::<RefCell<T>>()
+        .expect("instances wrap objects of their class's type")
+        .try_borrow_mut()
+        .map_err(|_| RuntimeErrorType::ObjectInUse(class.to_string()))
+}
+
+/// An instance of a host class, wrapping an object of the host
+pub struct HostInstance {
+    pub class: Rc<HostClass>,
+    object: Rc<dyn Any>,
+}
+
+impl HostInstance {
+    /// Reads a property, or binds a method to the instance
+    pub fn get(&self, name: &Token) -> HostResult {
+        let symbol = name.symbol();
+
+        if let Some(getter) = self.class.getters.get(&symbol) {
+            return getter(&*self.object);
+        }
+
+        match self.class.methods.get(&symbol) {
+            Some((arity, method)) => {
+                let method = Rc::clone(method);
+                let object = Rc::clone(&self.object);
+                let bound =
+                    move |ctx: &mut Ctx, arguments: &[Value]| method(ctx, &*object, arguments);
+
+                Ok(Value::HostFunction(Rc::new(HostFunction::new(
+                    &name.lexeme,
+                    *arity,
+                    bound,
+                ))))
+            }
+            None => Err(RuntimeErrorType::UndefinedProperty(name.lexeme.to_string())),
+        }
+    }
+
+    /// Assigns to a property. Host objects have a fixed set of properties,
+    /// so programs can not add fields to them.
+    pub fn set(&self, name: &Token, value: Value) -> HostResult<()> {
+        let symbol = name.symbol();
+
+        match self.class.setters.get(&symbol) {
+            Some(setter) => setter(&*self.object, value),
+            None if self.class.getters.contains_key(&symbol) => {
+                Err(RuntimeErrorType::ReadOnlyProperty(name.lexeme.to_string()))
+            }
+            None => Err(RuntimeErrorType::UndefinedProperty(name.lexeme.to_string())),
+        }
+    }
+
+    /// Whether two instances wrap the same object
+    pub fn same_object(&self, other: &HostInstance) -> bool {
+        Rc::ptr_eq(&self.object, &other.object)
+    }
+}
+
+impl std::fmt::Debug for HostInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HostInstance({})", self.class.name)
+    }
+}
+
+impl std::fmt::Display for HostInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
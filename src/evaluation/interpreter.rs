@@ -0,0 +1,1137 @@
+use crate::evaluation::class::{LoxClass, LoxInstance};
+use crate::evaluation::collector::Collector;
+use crate::evaluation::environment::Environment;
+use crate::evaluation::error::{RuntimeError, RuntimeErrorType, TraceFrame, Unwind};
+use crate::evaluation::function::LoxFunction;
+use crate::evaluation::host::Ctx;
+use crate::evaluation::native::{Native, STANDARD_LIBRARY};
+use crate::evaluation::value::Value;
+use crate::lexical_analysis::symbol::Symbol;
+use crate::lexical_analysis::token::Token;
+use crate::lexical_analysis::token_type::TokenType;
+use crate::syntax_analysis::expression::{Expr, ExprId};
+use crate::syntax_analysis::statement::Stmt;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{stdout, Write};
+use std::rc::Rc;
+
+/// How deeply calls may nest before the program is considered to be
+/// recursing without end
+const MAX_CALL_DEPTH: usize = 255;
+
+/// A function call being executed
+struct ActiveCall {
+    /// The name of the function called.
+    function: Symbol,
+    /// The line of the call, in the code of the caller.
+    line: usize,
+}
+
+/// A tree-walk interpreter which executes statements directly from the AST
+pub struct Interpreter {
+    /// The outermost environment, holding global variables.
+    globals: Rc<RefCell<Environment>>,
+    /// The environment of the scope currently being executed.
+    environment: Rc<RefCell<Environment>>,
+    /// How many scopes up each local variable reference of the code being
+    /// executed has to look, as worked out by the resolver. References
+    /// missing here are globals.
+    locals: Rc<HashMap<ExprId, usize>>,
+    /// The function calls currently being executed, innermost last.
+    calls: Vec<ActiveCall>,
+    /// Frees the objects kept alive only by reference cycles.
+    collector: Collector,
+    /// Where `print` writes to.
+    output: Box<dyn Write>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        let globals = Rc::new(RefCell::new(Environment::default()));
+
+        let mut interpreter = Self {
+            globals: Rc::clone(&globals),
+            environment: globals,
+            locals: Rc::default(),
+            calls: Vec::new(),
+            collector: Collector::default(),
+            output: Box::new(stdout()),
+        };
+
+        for &native in STANDARD_LIBRARY {
+            interpreter.define_native(native);
+        }
+
+        interpreter
+    }
+
+    /// Makes a native function available to programs as a global
+    pub fn define_native(&mut self, native: Native) {
+        self.define_global(native.name, Value::Native(native));
+    }
+
+    /// Defines a global variable, replacing any previous one of that name
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        self.globals
+            .borrow_mut()
+            .define(Symbol::intern(name), value);
+    }
+
+    pub fn global(&self, name: &str) -> Option<Value> {
+        // A name that was never interned can't have been defined
+        self.globals.borrow().lookup(Symbol::lookup(name)?)
+    }
+
+    /// Where `print` writes to
+    pub(crate) fn output(&mut self) -> &mut dyn Write {
+        self.output.as_mut()
+    }
+
+    /// Sends the output of `print` somewhere other than stdout
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output;
+    }
+
+    /// Makes every allocation collect garbage first, so that cycles are
+    /// broken as early as possible
+    pub fn set_gc_stress(&mut self, stress: bool) {
+        self.collector.set_stress(stress);
+    }
+
+    /// Sets the scope depths the resolver found for a program about to be
+    /// run. Functions keep those of the program they were declared in, so
+    /// they are dropped along with the program's last function.
+    pub fn resolve(&mut self, locals: HashMap<ExprId, usize>) {
+        self.locals = Rc::new(locals);
+    }
+
+    /// Executes a program, stopping at the first runtime error. If the
+    /// program ends with an expression statement, its value is returned.
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<Value, RuntimeError> {
+        let mut value = Value::Nil;
+
+        for stmt in statements {
+            let result = match stmt {
+                Stmt::Expression { expr } => self.evaluate(expr).map_err(Unwind::from),
+                stmt => self.execute(stmt).map(|()| Value::Nil),
+            };
+
+            match result {
+                Ok(result) => value = result,
+                Err(Unwind::Error(error)) => return Err(self.traced(error)),
+                // A `return` outside of any function ends the program
+                Err(Unwind::Return(_)) => return Ok(Value::Nil),
+            }
+        }
+
+        Ok(value)
+    }
+
+    /// Adds a trace to an error which reached the top level of the program.
+    /// Errors escaping a function call already have one.
+    fn traced(&self, error: RuntimeError) -> RuntimeError {
+        if error.trace().is_empty() {
+            let trace = self.stack_trace(error.span().line());
+            error.with_trace(trace)
+        } else {
+            error
+        }
+    }
+
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
+        match stmt {
+            Stmt::Block { statements } => {
+                let environment = Environment::new(Rc::clone(&self.environment));
+                let environment = Rc::new(RefCell::new(environment));
+                self.collector.track(&environment);
+                self.execute_block(statements, environment)
+            }
+            Stmt::Expression { expr } => {
+                self.evaluate(expr)?;
+                Ok(())
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if self.evaluate(condition)?.is_truthy() {
+                    self.execute(then_branch)
+                } else if let Some(else_branch) = else_branch {
+                    self.execute(else_branch)
+                } else {
+                    Ok(())
+                }
+            }
+            Stmt::Print { expr } => {
+                let value = self.evaluate(expr)?;
+                writeln!(self.output, "{}", value).map_err(|error| {
+                    RuntimeError::new(RuntimeErrorType::Output(error.kind()), expr.span()).into()
+                })
+            }
+            Stmt::Var { name, initializer } => {
+                let value = match initializer {
+                    Some(initializer) => self.evaluate(initializer)?,
+                    None => Value::Nil,
+                };
+
+                self.environment.borrow_mut().define(name.symbol(), value);
+                Ok(())
+            }
+            Stmt::While { condition, body } => {
+                while self.evaluate(condition)?.is_truthy() {
+                    self.execute(body)?;
+                }
+
+                Ok(())
+            }
+            Stmt::Function(declaration) => {
+                let function = LoxFunction::new(
+                    Rc::clone(declaration),
+                    Rc::clone(&self.environment),
+                    Rc::clone(&self.locals),
+                    false,
+                );
+                let function = Rc::new(function);
+                self.collector.track(&function);
+
+                self.environment
+                    .borrow_mut()
+                    .define(declaration.name.symbol(), Value::Function(function));
+                Ok(())
+            }
+            Stmt::Return { value, .. } => {
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::Nil,
+                };
+
+                Err(Unwind::Return(value))
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
+                let superclass = match superclass {
+                    Some(superclass) => {
+                        match self.look_up_variable(&superclass.name, superclass.id)? {
+                            Value::Class(class) => Some(class),
+                            _ => {
+                                return Err(RuntimeError::new(
+                                    RuntimeErrorType::SuperclassMustBeClass,
+                                    superclass.name.span(),
+                                )
+                                .into())
+                            }
+                        }
+                    }
+                    None => None,
+                };
+
+                self.environment
+                    .borrow_mut()
+                    .define(name.symbol(), Value::Nil);
+
+                // Methods of a subclass close over an extra scope binding `super`
+                let closure = match &superclass {
+                    Some(superclass) => {
+                        let mut environment = Environment::new(Rc::clone(&self.environment));
+                        environment.define(Symbol::SUPER, Value::Class(Rc::clone(superclass)));
+                        let environment = Rc::new(RefCell::new(environment));
+                        self.collector.track(&environment);
+                        environment
+                    }
+                    None => Rc::clone(&self.environment),
+                };
+
+                let methods = methods
+                    .iter()
+                    .map(|method| {
+                        let function = LoxFunction::new(
+                            Rc::clone(method),
+                            Rc::clone(&closure),
+                            Rc::clone(&self.locals),
+                            method.name.symbol() == Symbol::INIT,
+                        );
+                        let function = Rc::new(function);
+                        self.collector.track(&function);
+                        (method.name.symbol(), function)
+                    })
+                    .collect::<HashMap<_, _>>();
+
+                let class = Rc::new(LoxClass::new(name.lexeme.to_string(), superclass, methods));
+                self.collector.track(&class);
+                self.environment
+                    .borrow_mut()
+                    .assign(name, Value::Class(class))?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Executes statements in the given environment, restoring the current
+    /// environment afterwards even if a statement fails
+    fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<(), Unwind> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+
+        let result = statements.iter().try_for_each(|stmt| self.execute(stmt));
+
+        self.environment = previous;
+
+        result
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+        match expr {
+            Expr::Assign { name, value, id } => {
+                let value = self.evaluate(value)?;
+
+                match self.locals.get(id) {
+                    Some(&depth) => Environment::assign_at(
+                        &self.environment,
+                        depth,
+                        name.symbol(),
+                        value.clone(),
+                    ),
+                    None => self.globals.borrow_mut().assign(name, value.clone())?,
+                }
+
+                Ok(value)
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                Self::binary(operator, left, right)
+            }
+            Expr::Call {
+                callee,
+                parenthesis,
+                arguments,
+            } => {
+                let callee = self.evaluate(callee)?;
+
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.evaluate(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                self.call(callee, arguments, parenthesis)
+            }
+            Expr::Get { object, name } => match self.evaluate(object)? {
+                Value::Instance(instance) => {
+                    let value = LoxInstance::get(&instance, name)?;
+
+                    // Methods are bound to the instance anew on every access
+                    if let Value::Function(method) = &value {
+                        self.track_method(method);
+                    }
+                    Ok(value)
+                }
+                Value::HostInstance(instance) => instance
+                    .get(name)
+                    .map_err(|typ| RuntimeError::new(typ, name.span())),
+                _ => Err(RuntimeError::new(
+                    RuntimeErrorType::OnlyInstancesHaveProperties,
+                    name.span(),
+                )),
+            },
+            Expr::Group { expression } => self.evaluate(expression),
+            Expr::Interpolation { parts, .. } => {
+                let mut string = String::new();
+                for part in parts {
+                    string.push_str(&self.evaluate(part)?.to_string());
+                }
+                Ok(Value::String(Rc::from(string)))
+            }
+            Expr::Literal { value, .. } => Ok(value.as_ref().map_or(Value::Nil, Value::from)),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.evaluate(left)?;
+
+                // Short-circuit: the left operand decides the result on its own
+                // when it is truthy for `or`, or falsey for `and`.
+                if (operator.typ() == TokenType::Or) == left.is_truthy() {
+                    Ok(left)
+                } else {
+                    self.evaluate(right)
+                }
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                let object = self.evaluate(object)?;
+                if !matches!(object, Value::Instance(_) | Value::HostInstance(_)) {
+                    return Err(RuntimeError::new(
+                        RuntimeErrorType::OnlyInstancesHaveFields,
+                        name.span(),
+                    ));
+                }
+
+                let value = self.evaluate(value)?;
+                match object {
+                    Value::Instance(instance) => instance.borrow_mut().set(name, value.clone()),
+                    Value::HostInstance(instance) => instance
+                        .set(name, value.clone())
+                        .map_err(|typ| RuntimeError::new(typ, name.span()))?,
+                    _ => unreachable!("only instances have fields"),
+                }
+                Ok(value)
+            }
+            Expr::Super {
+                keyword,
+                method,
+                id,
+            } => {
+                let depth = self.locals[id];
+                let Value::Class(superclass) =
+                    Environment::get_at(&self.environment, depth, keyword.symbol())
+                else {
+                    unreachable!("'super' is always bound to a class");
+                };
+
+                // `this` is bound in the scope just inside the one binding `super`
+                let instance = Environment::get_at(&self.environment, depth - 1, Symbol::THIS);
+
+                match superclass.find_method(method.symbol()) {
+                    Some(function) => {
+                        let method = Rc::new(function.bind(instance));
+                        self.track_method(&method);
+                        Ok(Value::Function(method))
+                    }
+                    None => Err(RuntimeError::new(
+                        RuntimeErrorType::UndefinedProperty(method.lexeme.to_string()),
+                        method.span(),
+                    )),
+                }
+            }
+            Expr::This { keyword, id } => self.look_up_variable(keyword, *id),
+            Expr::Unary { operator, right } => {
+                let right = self.evaluate(right)?;
+
+                match operator.typ() {
+                    TokenType::Bang => Ok(Value::Boolean(!right.is_truthy())),
+                    TokenType::Minus => match right {
+                        Value::Number(n) => Ok(Value::Number(-n)),
+                        _ => Err(RuntimeError::new(
+                            RuntimeErrorType::OperandMustBeNumber,
+                            operator.span(),
+                        )),
+                    },
+                    _ => unreachable!("invalid unary operator {}", operator.lexeme),
+                }
+            }
+            Expr::Variable { name } => self.look_up_variable(&name.name, name.id),
+        }
+    }
+
+    fn look_up_variable(&self, name: &Token, id: ExprId) -> Result<Value, RuntimeError> {
+        match self.locals.get(&id) {
+            Some(&depth) => Ok(Environment::get_at(&self.environment, depth, name.symbol())),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    pub(crate) fn call(
+        &mut self,
+        callee: Value,
+        arguments: Vec<Value>,
+        parenthesis: &Token<'static>,
+    ) -> Result<Value, RuntimeError> {
+        let arity = match &callee {
+            Value::Function(function) => function.arity(),
+            Value::Native(native) => native.arity,
+            Value::Class(class) => class.arity(),
+            Value::HostFunction(function) => function.arity,
+            Value::HostClass(class) => class.arity(),
+            _ => {
+                return Err(RuntimeError::new(
+                    RuntimeErrorType::NotCallable,
+                    parenthesis.span(),
+                ))
+            }
+        };
+
+        if arguments.len() != arity {
+            return Err(RuntimeError::new(
+                RuntimeErrorType::ArityMismatch {
+                    expected: arity,
+                    got: arguments.len(),
+                },
+                parenthesis.span(),
+            ));
+        }
+
+        match callee {
+            Value::Function(function) => self.call_function(&function, arguments, parenthesis),
+            Value::Native(native) => (native.function)(&arguments.as_slice())
+                .map(Value::from)
+                .map_err(|typ| RuntimeError::from_host(typ, parenthesis.span())),
+            Value::HostFunction(function) => function
+                .call(&mut Ctx::new(self, parenthesis), &arguments)
+                .map_err(|typ| RuntimeError::from_host(typ, parenthesis.span())),
+            Value::HostClass(class) => class
+                .construct(&mut Ctx::new(self, parenthesis), &arguments)
+                .map_err(|typ| RuntimeError::new(typ, parenthesis.span())),
+            Value::Class(class) => {
+                let instance = Rc::new(RefCell::new(LoxInstance::new(Rc::clone(&class))));
+                self.collector.track(&instance);
+                let instance = Value::Instance(instance);
+
+                if let Some(initializer) = class.find_method(Symbol::INIT) {
+                    let initializer = Rc::new(initializer.bind(instance.clone()));
+                    self.track_method(&initializer);
+                    self.call_function(&initializer, arguments, parenthesis)?;
+                }
+
+                Ok(instance)
+            }
+            _ => unreachable!("only functions and classes have an arity"),
+        }
+    }
+
+    /// Runs a function body in a new environment enclosed by the function's
+    /// closure, with the parameters bound to the arguments. The caller has
+    /// already checked the number of arguments.
+    fn call_function(
+        &mut self,
+        function: &LoxFunction,
+        arguments: Vec<Value>,
+        parenthesis: &Token,
+    ) -> Result<Value, RuntimeError> {
+        if self.calls.len() >= MAX_CALL_DEPTH {
+            return Err(RuntimeError::new(
+                RuntimeErrorType::StackOverflow,
+                parenthesis.span(),
+            ));
+        }
+
+        let mut environment = Environment::new(Rc::clone(&function.closure));
+        for (parameter, argument) in function.declaration.arguments.iter().zip(arguments) {
+            environment.define(parameter.symbol(), argument);
+        }
+
+        let environment = Rc::new(RefCell::new(environment));
+        self.collector.track(&environment);
+
+        self.calls.push(ActiveCall {
+            function: function.declaration.name.symbol(),
+            line: parenthesis.span().line(),
+        });
+        // The body may have been declared in an earlier program
+        let locals = std::mem::replace(&mut self.locals, Rc::clone(&function.locals));
+        let result = self.execute_block(&function.declaration.body, environment);
+        self.locals = locals;
+
+        // The trace is taken before the call is popped, as it has to include
+        // the call the error happened in
+        let result = match result {
+            Err(Unwind::Error(error)) if error.trace().is_empty() => {
+                let trace = self.stack_trace(error.span().line());
+                Err(Unwind::Error(error.with_trace(trace)))
+            }
+            result => result,
+        };
+        self.calls.pop();
+
+        match result {
+            Err(Unwind::Error(error)) => Err(error),
+            // An initializer always hands back the instance it initialized
+            _ if function.is_initializer => Ok(function
+                .closure
+                .borrow()
+                .lookup(Symbol::THIS)
+                .expect("initializers are always bound to an instance")),
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+        }
+    }
+
+    /// Lists the active calls, innermost first, given the line being
+    /// executed in the innermost one
+    fn stack_trace(&self, line: usize) -> Vec<TraceFrame> {
+        let mut line = line;
+        let mut trace = Vec::new();
+
+        for call in self.calls.iter().rev() {
+            trace.push(TraceFrame::new(Some(call.function.to_string()), line));
+            line = call.line;
+        }
+
+        trace.push(TraceFrame::new(None, line));
+        trace
+    }
+
+    /// Tracks a method which was just bound to an instance, along with the
+    /// environment binding `this`
+    fn track_method(&mut self, method: &Rc<LoxFunction>) {
+        self.collector.track(&method.closure);
+        self.collector.track(method);
+    }
+
+    fn binary(operator: &Token, left: Value, right: Value) -> Result<Value, RuntimeError> {
+        match operator.typ() {
+            TokenType::EqualEqual => return Ok(Value::Boolean(left == right)),
+            TokenType::BangEqual => return Ok(Value::Boolean(left != right)),
+            TokenType::Plus => {
+                return match (left, right) {
+                    (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
+                    (Value::String(a), Value::String(b)) => {
+                        Ok(Value::String(Rc::from(format!("{}{}", a, b))))
+                    }
+                    _ => Err(RuntimeError::new(
+                        RuntimeErrorType::OperandsMustBeNumbersOrStrings,
+                        operator.span(),
+                    )),
+                }
+            }
+            _ => {}
+        }
+
+        let (Value::Number(a), Value::Number(b)) = (left, right) else {
+            return Err(RuntimeError::new(
+                RuntimeErrorType::OperandsMustBeNumbers,
+                operator.span(),
+            ));
+        };
+
+        match operator.typ() {
+            TokenType::Minus => Ok(Value::Number(a - b)),
+            TokenType::Star => Ok(Value::Number(a * b)),
+            TokenType::Slash => Ok(Value::Number(a / b)),
+            TokenType::Greater => Ok(Value::Boolean(a > b)),
+            TokenType::GreaterEqual => Ok(Value::Boolean(a >= b)),
+            TokenType::Less => Ok(Value::Boolean(a < b)),
+            TokenType::LessEqual => Ok(Value::Boolean(a <= b)),
+            _ => unreachable!("invalid binary operator {}", operator.lexeme),
+        }
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lexical_analysis::lexer::Lexer;
+    use crate::lexical_analysis::span::Span;
+    use crate::semantic_analysis::resolver::Resolver;
+    use crate::syntax_analysis::parser::Parser;
+
+    fn run(source: &str) -> (Interpreter, Result<(), RuntimeError>) {
+        let mut interpreter = Interpreter::new();
+        let result = run_in(&mut interpreter, source);
+
+        (interpreter, result)
+    }
+
+    /// Runs a program on an interpreter which may have run others before
+    fn run_in(interpreter: &mut Interpreter, source: &str) -> Result<(), RuntimeError> {
+        let mut lexer = Lexer::new(source);
+        let (tokens, errors) = lexer.scan_tokens();
+        assert!(errors.is_empty(), "Unexpected lexical errors: {:?}", errors);
+
+        let (statements, errors) = Parser::new(tokens).parse();
+        assert!(errors.is_empty(), "Unexpected parse errors: {:?}", errors);
+
+        let (locals, errors) = Resolver::new().resolve(&statements);
+        assert!(errors.is_empty(), "Unexpected resolve errors: {:?}", errors);
+
+        interpreter.resolve(locals);
+        interpreter.interpret(&statements).map(|_| ())
+    }
+
+    fn global(interpreter: &Interpreter, name: &str) -> Value {
+        interpreter
+            .globals
+            .borrow()
+            .lookup(Symbol::lookup(name).unwrap())
+            .unwrap()
+    }
+
+    fn assert_runtime_error(source: &str, expected: &str) {
+        let (_, result) = run(source);
+
+        assert_eq!(result.unwrap_err().to_string(), expected);
+    }
+
+    #[test]
+    fn test_functions_keep_their_program_resolved() {
+        let (mut interpreter, result) = run("fun f(a) { var b = a; return b; }");
+        assert!(result.is_ok());
+        let first = Rc::downgrade(&interpreter.locals);
+
+        assert!(run_in(&mut interpreter, "{ var a = 2; var x = f(a); }").is_ok());
+        assert!(first.upgrade().is_some());
+
+        // Nothing of the first program is left once its function is gone
+        assert!(run_in(&mut interpreter, "f = nil;").is_ok());
+        assert!(first.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_string_literals_share_their_text() {
+        let (interpreter, result) = run("fun f() { return \"text\"; } var a = f(); var b = f();");
+        assert!(result.is_ok());
+
+        match (global(&interpreter, "a"), global(&interpreter, "b")) {
+            (Value::String(a), Value::String(b)) => assert!(Rc::ptr_eq(&a, &b)),
+            values => panic!("Expected two strings, got {:?}", values),
+        }
+    }
+
+    #[test]
+    fn test_arithmetic_and_comparison() {
+        let (interpreter, result) = run("var a = (1 + 2) * 3 - 4 / 2; var b = a >= 7; var c = -a;");
+
+        assert!(result.is_ok());
+        assert_eq!(global(&interpreter, "a"), Value::Number(7.0));
+        assert_eq!(global(&interpreter, "b"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "c"), Value::Number(-7.0));
+    }
+
+    #[test]
+    fn test_strings_equality_and_truthiness() {
+        let (interpreter, result) = run(r#"var s = "foo" + "bar";
+               var eq = s == "foobar";
+               var mixed = 1 == "1";
+               var falsey = !nil;
+               var truthy = !!0;
+               var either = nil or "default";
+               var both = false and undefined;
+               var str = "shadowed";
+               var interpolated = "${s}: ${1 + 1} ${nil}";
+               var local;
+               { var inner = "in"; local = "${inner}side"; }"#);
+
+        assert!(result.is_ok());
+        assert_eq!(global(&interpreter, "s"), Value::String(Rc::from("foobar")));
+        assert_eq!(global(&interpreter, "eq"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "mixed"), Value::Boolean(false));
+        assert_eq!(global(&interpreter, "falsey"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "truthy"), Value::Boolean(true));
+        assert_eq!(
+            global(&interpreter, "either"),
+            Value::String(Rc::from("default"))
+        );
+        assert_eq!(global(&interpreter, "both"), Value::Boolean(false));
+        assert_eq!(
+            global(&interpreter, "interpolated"),
+            Value::String(Rc::from("foobar: 2 nil"))
+        );
+        assert_eq!(
+            global(&interpreter, "local"),
+            Value::String(Rc::from("inside"))
+        );
+    }
+
+    #[test]
+    fn test_block_scopes_and_loops() {
+        let (interpreter, result) = run("var a = 1;
+             var b = 0;
+             { var a = 10; b = a; }
+             var sum = 0;
+             for (var i = 0; i < 5; i = i + 1) {
+                 if (i == 2) sum = sum + 100; else sum = sum + i;
+             }");
+
+        assert!(result.is_ok());
+        assert_eq!(global(&interpreter, "a"), Value::Number(1.0));
+        assert_eq!(global(&interpreter, "b"), Value::Number(10.0));
+        assert_eq!(global(&interpreter, "sum"), Value::Number(108.0));
+    }
+
+    #[test]
+    fn test_recursive_functions() {
+        let (interpreter, result) = run(
+            "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+             fun isEven(n) { if (n == 0) return true; return isOdd(n - 1); }
+             fun isOdd(n) { if (n == 0) return false; return isEven(n - 1); }
+             var f = fib(15);
+             var even = isEven(10);",
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(global(&interpreter, "f"), Value::Number(610.0));
+        assert_eq!(global(&interpreter, "even"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_closures_and_return_unwinding() {
+        let (interpreter, result) = run("fun makeCounter() {
+                 var count = 0;
+                 fun increment() { count = count + 1; return count; }
+                 return increment;
+             }
+             var a = makeCounter();
+             var b = makeCounter();
+             a(); a();
+             var fromA = a();
+             var fromB = b();
+             fun find() {
+                 for (var i = 0; ; i = i + 1) {
+                     { if (i == 3) { return i; } }
+                 }
+             }
+             var found = find();
+             fun nothing() {}
+             var none = nothing();");
+
+        assert!(result.is_ok());
+        assert_eq!(global(&interpreter, "fromA"), Value::Number(3.0));
+        assert_eq!(global(&interpreter, "fromB"), Value::Number(1.0));
+        assert_eq!(global(&interpreter, "found"), Value::Number(3.0));
+        assert_eq!(global(&interpreter, "none"), Value::Nil);
+    }
+
+    #[test]
+    fn test_closures_use_resolved_bindings() {
+        let (interpreter, result) = run(r#"var a = "global";
+               var first;
+               var second;
+               {
+                   fun show() { return a; }
+                   first = show();
+                   var a = "block";
+                   second = show();
+               }"#);
+
+        assert!(result.is_ok());
+        assert_eq!(
+            global(&interpreter, "first"),
+            Value::String(Rc::from("global"))
+        );
+        assert_eq!(
+            global(&interpreter, "second"),
+            Value::String(Rc::from("global"))
+        );
+    }
+
+    #[test]
+    fn test_classes_and_inheritance() {
+        let (interpreter, result) = run(r#"class Animal {
+                   init(name) { this.name = name; }
+                   describe() { return this.name + " says " + this.sound(); }
+                   sound() { return "..."; }
+               }
+               class Dog < Animal {
+                   init(name) { super.init(name); this.tricks = 0; return; }
+                   sound() { return "woof"; }
+                   describe() { return super.describe() + "!"; }
+               }
+               var dog = Dog("Rex");
+               var described = dog.describe();
+               var bound = dog.sound;
+               dog.sound = "field";
+               var field = dog.sound;
+               var method = bound();
+               var reinit = dog.init("Max") == dog;
+               var name = dog.name;"#);
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(
+            global(&interpreter, "described"),
+            Value::String(Rc::from("Rex says woof!"))
+        );
+        assert_eq!(
+            global(&interpreter, "field"),
+            Value::String(Rc::from("field"))
+        );
+        assert_eq!(
+            global(&interpreter, "method"),
+            Value::String(Rc::from("woof"))
+        );
+        assert_eq!(global(&interpreter, "reinit"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "name"), Value::String(Rc::from("Max")));
+    }
+
+    #[test]
+    fn test_initializers() {
+        let (interpreter, result) = run(r#"class Point {
+                   init(x, y) {
+                       this.x = x;
+                       if (y == nil) return;
+                       this.y = y;
+                   }
+               }
+               var point = Point(1, 2);
+               var sum = point.x + point.y;
+               var early = Point(3, nil);
+               var early_is_instance = type(early);
+               var returned = point.init(5, 6) == point;
+               var x = point.x;
+               var init = point.init;
+               var rebound = init(7, 8) == point;"#);
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(global(&interpreter, "sum"), Value::Number(3.0));
+        assert_eq!(
+            global(&interpreter, "early_is_instance"),
+            Value::String(Rc::from("instance"))
+        );
+        assert_eq!(global(&interpreter, "returned"), Value::Boolean(true));
+        assert_eq!(global(&interpreter, "x"), Value::Number(5.0));
+        assert_eq!(global(&interpreter, "rebound"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_bound_methods_keep_this() {
+        let (interpreter, result) = run(r#"class Counter {
+                   init() { this.count = 0; }
+                   increment() { this.count = this.count + 1; return this.count; }
+               }
+               var a = Counter();
+               var b = Counter();
+               var increment = a.increment;
+               increment();
+               increment();
+               b.increment();
+               b.other = a.increment;
+               b.other();
+               var a_count = a.count;
+               var b_count = b.count;"#);
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(global(&interpreter, "a_count"), Value::Number(3.0));
+        assert_eq!(global(&interpreter, "b_count"), Value::Number(1.0));
+    }
+
+    #[test]
+    fn test_super_goes_up_the_chain() {
+        let (interpreter, result) = run(r#"class A {
+                   name() { return "A"; }
+                   describe() { return "A"; }
+               }
+               class B < A {
+                   describe() { return super.describe() + "B"; }
+               }
+               class C < B {
+                   describe() { return super.describe() + "C" + super.name(); }
+               }
+               var described = C().describe();
+               var inherited = C().name();"#);
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(
+            global(&interpreter, "described"),
+            Value::String(Rc::from("ABCA"))
+        );
+        assert_eq!(
+            global(&interpreter, "inherited"),
+            Value::String(Rc::from("A"))
+        );
+    }
+
+    #[test]
+    fn test_cycles_are_collected() {
+        let (mut interpreter, result) = run(r#"class Node {
+                   init(value) { this.value = value; this.self = this; }
+               }
+               fun make(value) {
+                   var node = Node(value);
+                   fun get() { return node; }
+                   node.get = get;
+                   return node;
+               }
+               var kept = make("kept");
+               for (var i = 0; i < 100; i = i + 1) make(i);"#);
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert!(interpreter.collector.len() > 100);
+
+        interpreter.collector.collect();
+        assert!(interpreter.collector.len() < 20);
+
+        // Objects still reachable from a global are left alone
+        let Value::Instance(kept) = global(&interpreter, "kept") else {
+            panic!("expected an instance");
+        };
+        assert_eq!(kept.borrow().fields().count(), 3);
+    }
+
+    #[test]
+    fn test_self_capturing_closures_are_collected() {
+        let (mut interpreter, result) = run(r#"fun make() {
+                   fun recurse(n) { if (n > 0) return recurse(n - 1); return recurse; }
+                   return recurse;
+               }
+               var kept = make();
+               for (var i = 0; i < 100; i = i + 1) make();"#);
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert!(interpreter.collector.len() > 200);
+
+        interpreter.collector.collect();
+        assert!(interpreter.collector.len() < 10);
+        assert!(run_in(&mut interpreter, "var same = kept(3) == kept;").is_ok());
+        assert_eq!(global(&interpreter, "same"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_class_instance_cycles_are_collected() {
+        // The method closes over the environment holding the instance, which
+        // refers back to its class
+        let (mut interpreter, result) = run(r#"fun make() {
+                   var instance;
+                   class Single { get() { return instance; } }
+                   instance = Single();
+                   return instance;
+               }
+               var kept = make();
+               for (var i = 0; i < 100; i = i + 1) make();"#);
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert!(interpreter.collector.len() > 300);
+
+        interpreter.collector.collect();
+        assert!(interpreter.collector.len() < 10);
+        assert!(run_in(&mut interpreter, "var same = kept.get() == kept;").is_ok());
+        assert_eq!(global(&interpreter, "same"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_values_held_by_the_host_are_kept() {
+        let (mut interpreter, result) = run(r#"class Node {}
+               var node = Node();
+               node.next = Node();
+               node.next.next = node;"#);
+        assert!(result.is_ok(), "{:?}", result);
+
+        // Only the host refers to the cycle once the global is gone
+        let held = global(&interpreter, "node");
+        assert!(run_in(&mut interpreter, "node = nil;").is_ok());
+        interpreter.collector.collect();
+
+        let Value::Instance(node) = &held else {
+            panic!("expected an instance");
+        };
+        let next = node.borrow().fields().next().cloned();
+        let Some(Value::Instance(next)) = next else {
+            panic!("expected the next node to survive");
+        };
+        assert_eq!(next.borrow().fields().count(), 1);
+
+        // Dropping the host's value leaves the cycle to the next collection
+        let before = interpreter.collector.len();
+        drop((held, next));
+        interpreter.collector.collect();
+        assert_eq!(interpreter.collector.len(), before - 2);
+    }
+
+    #[test]
+    fn test_natives() {
+        let (interpreter, result) = run(r#"var a = len("naïve");
+               var b = str(1.5) + str(nil);
+               var c = num("42") + 1;
+               var d = type(clock) + type("") + type(nil);
+               var e = clock() > 0;"#);
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(global(&interpreter, "a"), Value::Number(5.0));
+        assert_eq!(global(&interpreter, "b"), Value::String(Rc::from("1.5nil")));
+        assert_eq!(global(&interpreter, "c"), Value::Number(43.0));
+        assert_eq!(
+            global(&interpreter, "d"),
+            Value::String(Rc::from("functionstringnil"))
+        );
+        assert_eq!(global(&interpreter, "e"), Value::Boolean(true));
+
+        assert_runtime_error("len(1, 2);", "Expected 1 arguments but got 2.\n[line 1]");
+        assert_runtime_error(
+            "len(nil);",
+            "Expected a string as argument 1 but got a nil.\n[line 1]",
+        );
+    }
+
+    #[test]
+    fn test_stack_traces() {
+        let (_, result) = run("class Greeter {
+                 greet(name) { return \"Hello \" + name; }
+             }
+             fun welcome(name) {
+                 return Greeter().greet(name);
+             }
+             welcome(\"a\");
+             welcome(1);");
+        let error = result.unwrap_err();
+
+        let trace = error
+            .trace()
+            .iter()
+            .map(|frame| frame.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            trace,
+            [
+                "[line 2] in greet()",
+                "[line 5] in welcome()",
+                "[line 8] in script"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_runtime_error_spans() {
+        // Operator errors point at the operator
+        let (_, result) = run("var a = 1;\nprint a + \"b\";");
+        assert_eq!(result.unwrap_err().span(), Span::new(19, 20, 2, 9));
+
+        // Call errors point at the closing parenthesis
+        let (_, result) = run("fun f(a) {}\nf(1, 2);");
+        assert_eq!(result.unwrap_err().span(), Span::new(18, 19, 2, 7));
+
+        // Property errors point at the property name
+        let (_, result) = run("class A {}\nA().missing;");
+        assert_eq!(result.unwrap_err().span(), Span::new(15, 22, 2, 5));
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        assert_runtime_error("-\"a\";", "Operand must be a number.\n[line 1]");
+        assert_runtime_error("\n1 < nil;", "Operands must be numbers.\n[line 2]");
+        assert_runtime_error(
+            "1 + \"a\";",
+            "Operands must be two numbers or two strings.\n[line 1]",
+        );
+        assert_runtime_error("print x;", "Undefined variable 'x'.\n[line 1]");
+        assert_runtime_error("{ var x = 1; } x = 2;", "Undefined variable 'x'.\n[line 1]");
+        assert_runtime_error("\"a\"();", "Can only call functions and classes.\n[line 1]");
+        assert_runtime_error(
+            "fun f(a, b) {}\nf(1);",
+            "Expected 2 arguments but got 1.\n[line 2]",
+        );
+        assert_runtime_error(
+            "var a = 1;\nclass B < a {}",
+            "Superclass must be a class.\n[line 2]",
+        );
+        assert_runtime_error("1.field;", "Only instances have properties.\n[line 1]");
+        assert_runtime_error("\"s\".field = 1;", "Only instances have fields.\n[line 1]");
+        assert_runtime_error(
+            "class A {}\nA().missing;",
+            "Undefined property 'missing'.\n[line 2]",
+        );
+        assert_runtime_error(
+            "class A { init(a) {} }\nA();",
+            "Expected 1 arguments but got 0.\n[line 2]",
+        );
+    }
+}
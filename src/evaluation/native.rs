@@ -0,0 +1,260 @@
+use crate::evaluation::error::RuntimeErrorType;
+use std::io::stdin;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A function implemented in Rust which programs can call like any other.
+/// Natives are written against `Arguments` rather than either backend's
+/// values, so the same natives run on both.
+#[derive(Debug, Clone, Copy)]
+pub struct Native {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: fn(&dyn Arguments) -> Result<NativeValue, RuntimeErrorType>,
+}
+
+impl std::fmt::Display for Native {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+/// The values natives can return
+#[derive(Debug, Clone, PartialEq)]
+pub enum NativeValue {
+    Nil,
+    #[allow(unused)]
+    Boolean(bool),
+    Number(f64),
+    String(String),
+}
+
+/// The arguments of a call to a native, as seen through whichever backend
+/// is running it. The number of arguments has been checked against the
+/// native's arity before it is called.
+pub trait Arguments {
+    /// The kind of value an argument is, as `type()` names it
+    fn type_name(&self, index: usize) -> &'static str;
+
+    fn as_number(&self, index: usize) -> Option<f64>;
+
+    fn as_string(&self, index: usize) -> Option<&str>;
+
+    /// Formats an argument the way `print` shows it
+    fn display(&self, index: usize) -> String;
+
+    fn number(&self, index: usize) -> Result<f64, RuntimeErrorType> {
+        self.as_number(index)
+            .ok_or_else(|| self.wrong_type(index, "number"))
+    }
+
+    fn string(&self, index: usize) -> Result<&str, RuntimeErrorType> {
+        self.as_string(index)
+            .ok_or_else(|| self.wrong_type(index, "string"))
+    }
+
+    fn wrong_type(&self, index: usize, expected: &'static str) -> RuntimeErrorType {
+        RuntimeErrorType::WrongArgumentType {
+            position: index + 1,
+            expected,
+            got: self.type_name(index),
+        }
+    }
+}
+
+/// The natives every program can use
+pub const STANDARD_LIBRARY: &[Native] = &[
+    Native {
+        name: "clock",
+        arity: 0,
+        function: clock,
+    },
+    Native {
+        name: "input",
+        arity: 0,
+        function: read_line,
+    },
+    Native {
+        name: "readLine",
+        arity: 0,
+        function: read_line,
+    },
+    Native {
+        name: "len",
+        arity: 1,
+        function: len,
+    },
+    Native {
+        name: "str",
+        arity: 1,
+        function: str,
+    },
+    Native {
+        name: "num",
+        arity: 1,
+        function: num,
+    },
+    Native {
+        name: "type",
+        arity: 1,
+        function: type_name,
+    },
+    Native {
+        name: "exit",
+        arity: 1,
+        function: exit,
+    },
+];
+
+/// The number of seconds since the Unix epoch
+fn clock(_: &dyn Arguments) -> Result<NativeValue, RuntimeErrorType> {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("the clock is set after 1970");
+
+    Ok(NativeValue::Number(elapsed.as_secs_f64()))
+}
+
+/// Reads a line from standard input, without its line ending, or returns
+/// nil at the end of the input
+fn read_line(_: &dyn Arguments) -> Result<NativeValue, RuntimeErrorType> {
+    let mut line = String::new();
+
+    match stdin().read_line(&mut line) {
+        Ok(0) | Err(_) => Ok(NativeValue::Nil),
+        Ok(_) => {
+            let line = line.strip_suffix('\n').unwrap_or(&line);
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            Ok(NativeValue::String(line.to_string()))
+        }
+    }
+}
+
+/// The number of characters in a string
+fn len(arguments: &dyn Arguments) -> Result<NativeValue, RuntimeErrorType> {
+    let length = arguments.string(0)?.chars().count();
+    Ok(NativeValue::Number(length as f64))
+}
+
+fn str(arguments: &dyn Arguments) -> Result<NativeValue, RuntimeErrorType> {
+    Ok(NativeValue::String(arguments.display(0)))
+}
+
+/// Parses a string as a number, giving nil if it is not one. Infinities and
+/// NaN are not numbers a program could write, so they give nil as well.
+fn num(arguments: &dyn Arguments) -> Result<NativeValue, RuntimeErrorType> {
+    if let Some(number) = arguments.as_number(0) {
+        return Ok(NativeValue::Number(number));
+    }
+
+    Ok(match arguments.string(0)?.trim().parse::<f64>() {
+        Ok(number) if number.is_finite() => NativeValue::Number(number),
+        _ => NativeValue::Nil,
+    })
+}
+
+fn type_name(arguments: &dyn Arguments) -> Result<NativeValue, RuntimeErrorType> {
+    Ok(NativeValue::String(arguments.type_name(0).to_string()))
+}
+
+/// Stops the program with the given exit code
+fn exit(arguments: &dyn Arguments) -> Result<NativeValue, RuntimeErrorType> {
+    let code = arguments.number(0)?;
+
+    if code.fract() != 0.0 || code < i32::MIN as f64 || code > i32::MAX as f64 {
+        return Err(RuntimeErrorType::InvalidExitCode(code));
+    }
+
+    Err(RuntimeErrorType::Exit(code as i32))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Arguments which are all numbers or strings
+    struct Values(Vec<NativeValue>);
+
+    impl Arguments for Values {
+        fn type_name(&self, index: usize) -> &'static str {
+            match self.0[index] {
+                NativeValue::Nil => "nil",
+                NativeValue::Boolean(_) => "boolean",
+                NativeValue::Number(_) => "number",
+                NativeValue::String(_) => "string",
+            }
+        }
+
+        fn as_number(&self, index: usize) -> Option<f64> {
+            match self.0[index] {
+                NativeValue::Number(n) => Some(n),
+                _ => None,
+            }
+        }
+
+        fn as_string(&self, index: usize) -> Option<&str> {
+            match &self.0[index] {
+                NativeValue::String(s) => Some(s),
+                _ => None,
+            }
+        }
+
+        fn display(&self, index: usize) -> String {
+            format!("{:?}", self.0[index])
+        }
+    }
+
+    fn call(name: &str, arguments: Vec<NativeValue>) -> Result<NativeValue, RuntimeErrorType> {
+        let native = STANDARD_LIBRARY
+            .iter()
+            .find(|native| native.name == name)
+            .unwrap();
+        assert_eq!(native.arity, arguments.len());
+
+        (native.function)(&Values(arguments))
+    }
+
+    fn string(s: &str) -> NativeValue {
+        NativeValue::String(s.to_string())
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(
+            call("len", vec![string("naïve")]),
+            Ok(NativeValue::Number(5.0))
+        );
+        assert_eq!(
+            call("num", vec![string(" 1.5 ")]),
+            Ok(NativeValue::Number(1.5))
+        );
+        assert_eq!(call("num", vec![string("one")]), Ok(NativeValue::Nil));
+        for text in ["inf", "-infinity", "NaN", "1e999"] {
+            assert_eq!(call("num", vec![string(text)]), Ok(NativeValue::Nil));
+        }
+        assert_eq!(
+            call("num", vec![NativeValue::Number(2.0)]),
+            Ok(NativeValue::Number(2.0))
+        );
+        assert_eq!(call("type", vec![NativeValue::Nil]), Ok(string("nil")));
+    }
+
+    #[test]
+    fn test_argument_types() {
+        assert_eq!(
+            call("len", vec![NativeValue::Number(1.0)]),
+            Err(RuntimeErrorType::WrongArgumentType {
+                position: 1,
+                expected: "string",
+                got: "number",
+            })
+        );
+        assert_eq!(
+            call("exit", vec![NativeValue::Number(3.0)]),
+            Err(RuntimeErrorType::Exit(3))
+        );
+        assert_eq!(
+            call("exit", vec![NativeValue::Number(1.5)]),
+            Err(RuntimeErrorType::InvalidExitCode(1.5))
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use crate::evaluation::class::{LoxClass, LoxInstance};
+use crate::evaluation::function::LoxFunction;
+use crate::evaluation::host::{HostClass, HostFunction, HostInstance};
+use crate::evaluation::native::{Arguments, Native, NativeValue};
+use crate::lexical_analysis::token::Literal;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The values a Lox program can compute at runtime
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    String(Rc<str>),
+    Function(Rc<LoxFunction>),
+    Native(Native),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    /// A Rust closure the host made available to the program.
+    HostFunction(Rc<HostFunction>),
+    HostClass(Rc<HostClass>),
+    HostInstance(Rc<HostInstance>),
+}
+
+impl Value {
+    /// `false` and `nil` are falsey, everything else is truthy
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Boolean(false))
+    }
+
+    /// The name of the value's type, as `type()` gives it
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Boolean(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Function(_) | Value::Native(_) | Value::HostFunction(_) => "function",
+            Value::Class(_) | Value::HostClass(_) => "class",
+            Value::Instance(_) | Value::HostInstance(_) => "instance",
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            // Values of the same literal share their text
+            (Value::String(a), Value::String(b)) => Rc::ptr_eq(a, b) || a == b,
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Native(a), Value::Native(b)) => a.name == b.name,
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            (Value::HostFunction(a), Value::HostFunction(b)) => Rc::ptr_eq(a, b),
+            (Value::HostClass(a), Value::HostClass(b)) => Rc::ptr_eq(a, b),
+            (Value::HostInstance(a), Value::HostInstance(b)) => a.same_object(b),
+            _ => false,
+        }
+    }
+}
+
+impl From<&Literal> for Value {
+    fn from(literal: &Literal) -> Self {
+        match literal {
+            Literal::String(s) => Value::String(Rc::clone(s)),
+            Literal::Number(n) => Value::Number(*n),
+            Literal::Boolean(b) => Value::Boolean(*b),
+            Literal::Nil => Value::Nil,
+        }
+    }
+}
+
+impl From<NativeValue> for Value {
+    fn from(value: NativeValue) -> Self {
+        match value {
+            NativeValue::Nil => Value::Nil,
+            NativeValue::Boolean(b) => Value::Boolean(b),
+            NativeValue::Number(n) => Value::Number(n),
+            NativeValue::String(s) => Value::String(Rc::from(s)),
+        }
+    }
+}
+
+impl Arguments for &[Value] {
+    fn type_name(&self, index: usize) -> &'static str {
+        self[index].type_name()
+    }
+
+    fn as_number(&self, index: usize) -> Option<f64> {
+        match self[index] {
+            Value::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    fn as_string(&self, index: usize) -> Option<&str> {
+        match &self[index] {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn display(&self, index: usize) -> String {
+        self[index].to_string()
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Function(function) => write!(f, "{}", function),
+            Value::Native(native) => write!(f, "{}", native),
+            Value::Class(class) => write!(f, "{}", class),
+            Value::Instance(instance) => write!(f, "{}", instance.borrow()),
+            Value::HostFunction(function) => write!(f, "{}", function),
+            Value::HostClass(class) => write!(f, "{}", class),
+            Value::HostInstance(instance) => write!(f, "{}", instance),
+        }
+    }
+}
@@ -1,4 +1,6 @@
 pub mod error;
 pub mod lexer;
+pub mod span;
+pub mod symbol;
 pub mod token;
 pub mod token_type;
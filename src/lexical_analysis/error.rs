@@ -1,30 +1,97 @@
+use crate::diagnostics::Diagnostic;
+use crate::lexical_analysis::span::Span;
+
 #[derive(Debug, Clone)]
 pub struct LexicalError {
     typ: LexicalErrorType,
-    line: usize,
+    span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub enum LexicalErrorType {
     UnexpectedCharacter(char),
     UnterminatedString,
+    InvalidEscape(char),
+    InvalidUnicodeEscape,
+    /// A number which does not follow any of the number formats, and why.
+    MalformedNumber(String),
+    UnterminatedComment,
 }
 
 impl LexicalError {
-    pub fn new(typ: LexicalErrorType, line: usize) -> Self {
-        Self { typ, line }
+    pub fn new(typ: LexicalErrorType, span: Span) -> Self {
+        Self { typ, span }
+    }
+
+    pub fn typ(&self) -> &LexicalErrorType {
+        &self.typ
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
     }
 }
 
-impl std::fmt::Display for LexicalError {
+impl LexicalErrorType {
+    pub fn code(&self) -> &'static str {
+        match self {
+            LexicalErrorType::UnexpectedCharacter(_) => "E001",
+            LexicalErrorType::UnterminatedString => "E002",
+            LexicalErrorType::InvalidEscape(_) => "E003",
+            LexicalErrorType::InvalidUnicodeEscape => "E004",
+            LexicalErrorType::MalformedNumber(_) => "E005",
+            LexicalErrorType::UnterminatedComment => "E006",
+        }
+    }
+
+    pub fn help(&self) -> Option<&'static str> {
+        match self {
+            LexicalErrorType::UnexpectedCharacter(_) => None,
+            LexicalErrorType::UnterminatedString => {
+                Some("strings must be closed with a '\"' before the end of the file")
+            }
+            LexicalErrorType::InvalidEscape(_) => {
+                Some("the escapes are \\n, \\t, \\r, \\0, \\\", \\\\, \\$ and \\u{...}")
+            }
+            LexicalErrorType::InvalidUnicodeEscape => {
+                Some("write a code point as one to six hex digits, e.g. \\u{1F600}")
+            }
+            LexicalErrorType::MalformedNumber(_) => {
+                Some("numbers are written like 42, 4.2, 4.2e-1, 0x2A, 0b101010 or 1_000")
+            }
+            LexicalErrorType::UnterminatedComment => {
+                Some("block comments nest, so every '/*' needs its own '*/'")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for LexicalErrorType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self.typ {
-            LexicalErrorType::UnexpectedCharacter(c) => {
-                write!(f, "[line {}] Error: Unexpected character: {}", self.line, c)
+        match self {
+            LexicalErrorType::UnexpectedCharacter(c) => write!(f, "Unexpected character: {}", c),
+            LexicalErrorType::UnterminatedString => write!(f, "Unterminated string."),
+            LexicalErrorType::InvalidEscape(c) => {
+                write!(f, "Invalid escape sequence: \\{}", c.escape_debug())
             }
-            LexicalErrorType::UnterminatedString => {
-                write!(f, "[line {}] Error: Unterminated string.", self.line)
+            LexicalErrorType::InvalidUnicodeEscape => write!(f, "Invalid unicode escape."),
+            LexicalErrorType::MalformedNumber(reason) => {
+                write!(f, "Malformed number: {}.", reason)
             }
+            LexicalErrorType::UnterminatedComment => write!(f, "Unterminated block comment."),
         }
     }
 }
+
+impl std::fmt::Display for LexicalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[line {}] Error: {}", self.span.line(), self.typ)
+    }
+}
+
+impl From<&LexicalError> for Diagnostic {
+    fn from(error: &LexicalError) -> Self {
+        Diagnostic::new(error.typ.code(), error.typ.to_string(), error.span())
+            .with_help(error.typ.help())
+    }
+}
@@ -1,300 +1,650 @@
 use crate::lexical_analysis::error::{LexicalError, LexicalErrorType};
+use crate::lexical_analysis::span::Span;
 use crate::lexical_analysis::token::{Literal, Token};
 use crate::lexical_analysis::token_type::TokenType;
+use std::collections::VecDeque;
+use std::rc::Rc;
 use std::str::FromStr;
 
-#[allow(unused)]
-pub struct Lexer {
+/// Turns source code into tokens. The lexer is an iterator, scanning tokens
+/// as they are asked for; `scan_tokens` collects all of them at once.
+pub struct Lexer<'src> {
     /// The entire source code string.
-    source: String,
-    /// The index of the first character in the lexeme being processed.
+    source: &'src str,
+    /// The byte offset of the first character in the lexeme being processed.
     start: usize,
-    /// The index of the current character in the lexeme being processed.
+    /// The byte offset of the current character in the lexeme being processed.
     current: usize,
-    /// The line number to which the current lexeme belongs.
+    /// The line number of the current character.
     line: usize,
-    /// A vector to store any lexical errors encountered during scanning.
-    errors: Vec<LexicalError>,
+    /// The column of the current character within its line.
+    column: usize,
+    /// The line on which the lexeme being processed starts.
+    start_line: usize,
+    /// The column at which the lexeme being processed starts.
+    start_column: usize,
+    /// Tokens and errors scanned but not yet handed out. A lexeme makes up
+    /// at most one token, but a string can hold several bad escapes.
+    pending: VecDeque<Result<Token<'src>, LexicalError>>,
+    /// The lines of the doc comments waiting for the next token.
+    docs: Vec<String>,
+    /// Whether the `Eof` token has been scanned.
+    finished: bool,
+    /// How many braces are open within each interpolated expression being
+    /// scanned, innermost last. A `}` with none open resumes its string.
+    interpolations: Vec<usize>,
 }
 
-impl Lexer {
-    pub fn new(source: &str) -> Self {
+impl<'src> Lexer<'src> {
+    pub fn new(source: &'src str) -> Self {
         Self {
-            source: source.to_string(),
+            source,
             start: 0,
             current: 0,
             line: 1,
-            errors: Vec::new(),
+            column: 1,
+            start_line: 1,
+            start_column: 1,
+            pending: VecDeque::new(),
+            docs: Vec::new(),
+            finished: false,
+            interpolations: Vec::new(),
         }
     }
 
-    #[allow(unused)]
-    pub fn scan_tokens(&mut self) -> (Vec<Token>, Vec<LexicalError>) {
+    /// Scans the source from the given byte offset on. The text before it
+    /// is only counted, so the spans of the tokens point into the whole
+    /// source.
+    pub fn starting_at(source: &'src str, offset: usize) -> Self {
+        let before = &source[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+
+        Self {
+            start: offset,
+            current: offset,
+            line,
+            column,
+            start_line: line,
+            start_column: column,
+            ..Self::new(source)
+        }
+    }
+
+    /// Scans the source as the tokens are asked for, like the lexer itself,
+    /// but puts the errors aside so that only tokens are handed out
+    pub fn tokens<'a>(
+        self,
+        errors: &'a mut Vec<LexicalError>,
+    ) -> impl Iterator<Item = Token<'src>> + 'a
+    where
+        'src: 'a,
+    {
+        self.filter_map(|result| result.map_err(|error| errors.push(error)).ok())
+    }
+
+    /// Scans the whole source, separating the tokens from the errors
+    pub fn scan_tokens(&mut self) -> (Vec<Token<'src>>, Vec<LexicalError>) {
         let mut tokens = Vec::new();
         let mut errors = Vec::new();
 
-        while self.current < self.source.len() {
-            self.start = self.current;
-            let ch = self.advance();
+        for result in self {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(error) => errors.push(error),
+            }
+        }
+
+        (tokens, errors)
+    }
 
-            match ch {
-                '(' => tokens.push(Token::new(
-                    TokenType::LeftParen,
-                    ch.to_string(),
-                    None,
-                    self.line,
-                )),
-                ')' => tokens.push(Token::new(
-                    TokenType::RightParen,
-                    ch.to_string(),
-                    None,
-                    self.line,
-                )),
-                '{' => tokens.push(Token::new(
+    /// Scans the lexeme at the current character
+    fn scan_token(&mut self) {
+        self.start_lexeme();
+        let ch = self.advance();
+
+        match ch {
+            '(' => self.add_token(Token::new(
+                TokenType::LeftParen,
+                self.lexeme(),
+                None,
+                self.span(),
+            )),
+            ')' => self.add_token(Token::new(
+                TokenType::RightParen,
+                self.lexeme(),
+                None,
+                self.span(),
+            )),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+
+                self.add_token(Token::new(
                     TokenType::LeftBrace,
-                    ch.to_string(),
-                    None,
-                    self.line,
-                )),
-                '}' => tokens.push(Token::new(
-                    TokenType::RightBrace,
-                    ch.to_string(),
-                    None,
-                    self.line,
-                )),
-                ',' => tokens.push(Token::new(
-                    TokenType::Comma,
-                    ch.to_string(),
-                    None,
-                    self.line,
-                )),
-                '.' => tokens.push(Token::new(TokenType::Dot, ch.to_string(), None, self.line)),
-                '-' => tokens.push(Token::new(
-                    TokenType::Minus,
-                    ch.to_string(),
-                    None,
-                    self.line,
-                )),
-                '+' => tokens.push(Token::new(TokenType::Plus, ch.to_string(), None, self.line)),
-                ';' => tokens.push(Token::new(
-                    TokenType::Semicolon,
-                    ch.to_string(),
+                    self.lexeme(),
                     None,
-                    self.line,
-                )),
-                '*' => tokens.push(Token::new(TokenType::Star, ch.to_string(), None, self.line)),
-                '=' => {
-                    if self.advance_if_equal('=') {
-                        tokens.push(Token::new(
-                            TokenType::EqualEqual,
-                            "==".to_string(),
-                            None,
-                            self.line,
-                        ))
-                    } else {
-                        tokens.push(Token::new(
-                            TokenType::Equal,
-                            ch.to_string(),
-                            None,
-                            self.line,
-                        ))
-                    }
+                    self.span(),
+                ))
+            }
+            '}' => match self.interpolations.last_mut() {
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.string();
                 }
-                '!' => {
-                    if self.advance_if_equal('=') {
-                        tokens.push(Token::new(
-                            TokenType::BangEqual,
-                            "!=".to_string(),
-                            None,
-                            self.line,
-                        ))
-                    } else {
-                        tokens.push(Token::new(TokenType::Bang, ch.to_string(), None, self.line))
+                depth => {
+                    if let Some(depth) = depth {
+                        *depth -= 1;
                     }
+
+                    self.add_token(Token::new(
+                        TokenType::RightBrace,
+                        self.lexeme(),
+                        None,
+                        self.span(),
+                    ))
                 }
-                '>' => {
-                    if self.advance_if_equal('=') {
-                        tokens.push(Token::new(
-                            TokenType::GreaterEqual,
-                            ">=".to_string(),
-                            None,
-                            self.line,
-                        ))
-                    } else {
-                        tokens.push(Token::new(
-                            TokenType::Greater,
-                            ch.to_string(),
-                            None,
-                            self.line,
-                        ))
-                    }
+            },
+            ',' => self.add_token(Token::new(
+                TokenType::Comma,
+                self.lexeme(),
+                None,
+                self.span(),
+            )),
+            '.' => self.add_token(Token::new(TokenType::Dot, self.lexeme(), None, self.span())),
+            '-' => self.add_token(Token::new(
+                TokenType::Minus,
+                self.lexeme(),
+                None,
+                self.span(),
+            )),
+            '+' => self.add_token(Token::new(
+                TokenType::Plus,
+                self.lexeme(),
+                None,
+                self.span(),
+            )),
+            ';' => self.add_token(Token::new(
+                TokenType::Semicolon,
+                self.lexeme(),
+                None,
+                self.span(),
+            )),
+            '*' => self.add_token(Token::new(
+                TokenType::Star,
+                self.lexeme(),
+                None,
+                self.span(),
+            )),
+            '=' => {
+                if self.advance_if_equal('=') {
+                    self.add_token(Token::new(
+                        TokenType::EqualEqual,
+                        self.lexeme(),
+                        None,
+                        self.span(),
+                    ))
+                } else {
+                    self.add_token(Token::new(
+                        TokenType::Equal,
+                        self.lexeme(),
+                        None,
+                        self.span(),
+                    ))
                 }
-                '<' => {
-                    if self.advance_if_equal('=') {
-                        tokens.push(Token::new(
-                            TokenType::LessEqual,
-                            "<=".to_string(),
-                            None,
-                            self.line,
-                        ))
-                    } else {
-                        tokens.push(Token::new(TokenType::Less, ch.to_string(), None, self.line))
-                    }
+            }
+            '!' => {
+                if self.advance_if_equal('=') {
+                    self.add_token(Token::new(
+                        TokenType::BangEqual,
+                        self.lexeme(),
+                        None,
+                        self.span(),
+                    ))
+                } else {
+                    self.add_token(Token::new(
+                        TokenType::Bang,
+                        self.lexeme(),
+                        None,
+                        self.span(),
+                    ))
                 }
-                '/' => {
-                    if self.advance_if_equal('/') {
-                        while self.current < self.source.len() && !self.current_char_matches('\n') {
-                            self.current += 1;
-                        }
-                    } else {
-                        tokens.push(Token::new(
-                            TokenType::Slash,
-                            ch.to_string(),
-                            None,
-                            self.line,
-                        ))
-                    }
+            }
+            '>' => {
+                if self.advance_if_equal('=') {
+                    self.add_token(Token::new(
+                        TokenType::GreaterEqual,
+                        self.lexeme(),
+                        None,
+                        self.span(),
+                    ))
+                } else {
+                    self.add_token(Token::new(
+                        TokenType::Greater,
+                        self.lexeme(),
+                        None,
+                        self.span(),
+                    ))
+                }
+            }
+            '<' => {
+                if self.advance_if_equal('=') {
+                    self.add_token(Token::new(
+                        TokenType::LessEqual,
+                        self.lexeme(),
+                        None,
+                        self.span(),
+                    ))
+                } else {
+                    self.add_token(Token::new(
+                        TokenType::Less,
+                        self.lexeme(),
+                        None,
+                        self.span(),
+                    ))
                 }
-                '\n' => self.line += 1,
-                ch if ch.is_whitespace() => continue,
-                '"' => {
-                    while self.current < self.source.len() && !self.current_char_matches('"') {
-                        // Support multi-line strings
-                        if self.current_char_matches('\n') {
-                            self.line += 1;
-                        }
+            }
+            '/' => {
+                if self.advance_if_equal('/') {
+                    let is_doc = self.current_char_matches('/') && self.peek_next() != Some('/');
 
+                    while !self.is_at_end() && !self.current_char_matches('\n') {
                         self.advance();
                     }
 
-                    if self.current >= self.source.len() {
-                        errors.push(LexicalError::new(
-                            LexicalErrorType::UnterminatedString,
-                            self.line,
-                        ))
-                    } else {
-                        self.advance();
-
-                        tokens.push(Token::new(
-                            TokenType::String,
-                            self.source[self.start..self.current].to_string(),
-                            Some(Literal::String(
-                                self.source[self.start + 1..self.current - 1].to_string(),
-                            )),
-                            self.line,
-                        ))
+                    if is_doc {
+                        let line = &self.source[self.start + 3..self.current];
+                        self.docs
+                            .push(line.strip_prefix(' ').unwrap_or(line).to_string());
                     }
+                } else if self.advance_if_equal('*') {
+                    self.block_comment();
+                } else {
+                    self.add_token(Token::new(
+                        TokenType::Slash,
+                        self.lexeme(),
+                        None,
+                        self.span(),
+                    ))
+                }
+            }
+            '\n' => self.new_line(),
+            ch if ch.is_whitespace() => {}
+            '"' => self.string(),
+            ch if ch.is_ascii_digit() => match self.number(ch) {
+                Ok(number) => self.add_token(Token::new(
+                    TokenType::Number,
+                    self.lexeme(),
+                    Some(Literal::Number(number)),
+                    self.span(),
+                )),
+                Err(typ) => self.add_error(LexicalError::new(typ, self.span())),
+            },
+            ch if ch.is_alphanumeric() || ch == '_' => {
+                while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                    self.advance();
                 }
-                ch if ch.is_ascii_digit() => {
-                    while self.current < self.source.len()
-                        && self
-                            .source
-                            .chars()
-                            .nth(self.current)
-                            .unwrap()
-                            .is_ascii_digit()
-                    {
-                        self.advance();
-                    }
 
-                    if self.current_char_matches('.')
-                        && self.current + 1 < self.source.len()
-                        && self
-                            .source
-                            .chars()
-                            .nth(self.current + 1)
-                            .unwrap()
-                            .is_ascii_digit()
-                    {
-                        self.advance();
+                let text = self.lexeme();
+                let typ = TokenType::from_keyword(text).unwrap_or(TokenType::Identifier);
 
-                        while self.current < self.source.len()
-                            && self
-                                .source
-                                .chars()
-                                .nth(self.current)
-                                .unwrap()
-                                .is_ascii_digit()
-                        {
-                            self.advance();
-                        }
-                    }
+                self.add_token(Token::name(typ, text, self.span()))
+            }
+            _ => self.add_error(LexicalError::new(
+                LexicalErrorType::UnexpectedCharacter(ch),
+                self.span(),
+            )),
+        }
+    }
 
-                    tokens.push(Token::new(
-                        TokenType::Number,
-                        self.source[self.start..self.current].to_string(),
-                        match f64::from_str(&self.source[self.start..self.current]) {
-                            Ok(number) => Some(Literal::Number(number)),
-                            Err(_) => None,
-                        },
-                        self.line,
-                    ))
+    /// Ends the token stream once the whole source has been scanned
+    fn finish(&mut self) {
+        self.start_lexeme();
+
+        // A string with an interpolated expression left open never ended
+        if !self.interpolations.is_empty() {
+            self.add_error(LexicalError::new(
+                LexicalErrorType::UnterminatedString,
+                self.span(),
+            ));
+        }
+
+        self.add_token(Token::new(TokenType::Eof, "", None, self.span()));
+        self.finished = true;
+    }
+
+    /// Queues a scanned token, documented by the doc comments right before it
+    fn add_token(&mut self, token: Token<'src>) {
+        let token = if self.docs.is_empty() {
+            token
+        } else {
+            let doc = Rc::from(self.docs.join("\n"));
+            self.docs.clear();
+            token.with_doc(doc)
+        };
+
+        self.pending.push_back(Ok(token));
+    }
+
+    fn add_error(&mut self, error: LexicalError) {
+        self.pending.push_back(Err(error));
+    }
+
+    /// Skips a block comment after its opening `/*`. Block comments nest, so
+    /// that code containing them can be commented out as a whole.
+    fn block_comment(&mut self) {
+        let mut depth = 1;
+
+        while depth > 0 {
+            match self.peek() {
+                None => {
+                    self.add_error(LexicalError::new(
+                        LexicalErrorType::UnterminatedComment,
+                        self.span(),
+                    ));
+                    return;
                 }
-                ch if ch.is_alphanumeric() || ch == '_' => {
-                    while self.current < self.source.len()
-                        && (self.current_char_matches('_')
-                            || self
-                                .source
-                                .chars()
-                                .nth(self.current)
-                                .unwrap()
-                                .is_alphanumeric())
-                    {
-                        self.advance();
+                Some('/') if self.peek_next() == Some('*') => {
+                    self.advance();
+                    self.advance();
+                    depth += 1;
+                }
+                Some('*') if self.peek_next() == Some('/') => {
+                    self.advance();
+                    self.advance();
+                    depth -= 1;
+                }
+                Some(_) => {
+                    if self.advance() == '\n' {
+                        self.new_line();
                     }
+                }
+            }
+        }
+    }
 
-                    let text = &self.source[self.start..self.current];
-                    let typ =
-                        TokenType::from_keyword(text).unwrap_or_else(|| TokenType::Identifier);
+    /// Scans the rest of a string, from its opening quote or from the `}`
+    /// closing an interpolated expression within it. The string ends at its
+    /// closing quote, or as an `Interpolation` token at the next `${`.
+    fn string(&mut self) {
+        let mut value = String::new();
 
-                    tokens.push(Token::new(typ, text.to_string(), None, self.line))
+        let typ = loop {
+            match self.peek() {
+                None => {
+                    self.add_error(LexicalError::new(
+                        LexicalErrorType::UnterminatedString,
+                        self.span(),
+                    ));
+                    // Nor can the strings this one is interpolated into end
+                    self.interpolations.clear();
+                    return;
                 }
-                _ => errors.push(LexicalError::new(
-                    LexicalErrorType::UnexpectedCharacter(ch),
-                    self.line,
-                )),
+                Some('"') => {
+                    self.advance();
+                    break TokenType::String;
+                }
+                Some('$') if self.peek_next() == Some('{') => {
+                    self.advance();
+                    self.advance();
+                    self.interpolations.push(0);
+                    break TokenType::Interpolation;
+                }
+                Some('\\') if self.peek_next().is_some() => match self.escape() {
+                    Ok(ch) => value.push(ch),
+                    Err(error) => self.add_error(error),
+                },
+                Some(_) => {
+                    // Support multi-line strings
+                    let ch = self.advance();
+                    if ch == '\n' {
+                        self.new_line();
+                    }
+                    value.push(ch);
+                }
+            }
+        };
+
+        self.add_token(Token::new(
+            typ,
+            self.lexeme(),
+            Some(Literal::String(Rc::from(value))),
+            self.span(),
+        ))
+    }
+
+    /// Consumes an escape sequence within a string, giving the character it
+    /// stands for
+    fn escape(&mut self) -> Result<char, LexicalError> {
+        let (start, line, column) = (self.current, self.line, self.column);
+        self.advance();
+
+        let ch = self.advance();
+        let escaped = match ch {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '"' | '\\' | '$' => Some(ch),
+            'u' => {
+                return self.unicode_escape().ok_or_else(|| {
+                    LexicalError::new(
+                        LexicalErrorType::InvalidUnicodeEscape,
+                        Span::new(start, self.current, line, column),
+                    )
+                })
             }
+            _ => None,
+        };
+
+        let span = Span::new(start, self.current, line, column);
+        if ch == '\n' {
+            self.new_line();
         }
 
-        tokens.push(Token::new(TokenType::Eof, "".to_string(), None, self.line));
+        escaped.ok_or_else(|| LexicalError::new(LexicalErrorType::InvalidEscape(ch), span))
+    }
 
-        (tokens, errors)
+    /// Consumes the `{...}` of a `\u{...}` escape, giving the character with
+    /// that code point if it is a valid one
+    fn unicode_escape(&mut self) -> Option<char> {
+        if !self.advance_if_equal('{') {
+            return None;
+        }
+
+        let digits_start = self.current;
+        while self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+            self.advance();
+        }
+        let digits = self.source[digits_start..self.current].to_string();
+
+        if !self.advance_if_equal('}') || digits.is_empty() || digits.len() > 6 {
+            return None;
+        }
+
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
     }
 
+    /// Scans the rest of a number, given its first digit. Besides decimals
+    /// such as `1_000.5e-3`, numbers may be written in hexadecimal as `0xFF`
+    /// or in binary as `0b1010`.
+    fn number(&mut self, first: char) -> Result<f64, LexicalErrorType> {
+        let radix = match (first, self.peek()) {
+            ('0', Some('x' | 'X')) => Some((16, "hexadecimal")),
+            ('0', Some('b' | 'B')) => Some((2, "binary")),
+            _ => None,
+        };
+
+        if let Some((radix, name)) = radix {
+            self.advance();
+
+            // Anything which could belong to the number is taken in, so that
+            // a stray digit is reported rather than becoming another token
+            while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                self.advance();
+            }
+
+            let digits = &self.source[self.start + 2..self.current];
+            if digits.is_empty() {
+                return Err(LexicalErrorType::MalformedNumber(format!(
+                    "{} number has no digits",
+                    name
+                )));
+            }
+            Self::check_separators(digits, radix)?;
+
+            return digits
+                .chars()
+                .filter(|&c| c != '_')
+                .try_fold(0.0, |value, c| match c.to_digit(radix) {
+                    Some(digit) => Ok(value * radix as f64 + digit as f64),
+                    None => Err(LexicalErrorType::MalformedNumber(format!(
+                        "'{}' is not a {} digit",
+                        c, name
+                    ))),
+                });
+        }
+
+        self.decimal_digits();
+
+        if self.current_char_matches('.') && self.peek_next().is_some_and(|c| c.is_ascii_digit()) {
+            self.advance();
+            self.decimal_digits();
+        }
+
+        if self.advance_if_equal('e') || self.advance_if_equal('E') {
+            if !self.advance_if_equal('+') {
+                self.advance_if_equal('-');
+            }
+
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(LexicalErrorType::MalformedNumber(
+                    "exponent has no digits".to_string(),
+                ));
+            }
+            self.decimal_digits();
+        }
+
+        let text = &self.source[self.start..self.current];
+        Self::check_separators(text, 10)?;
+
+        Ok(f64::from_str(&text.replace('_', "")).expect("scanned numbers are valid floats"))
+    }
+
+    fn decimal_digits(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '_') {
+            self.advance();
+        }
+    }
+
+    /// Checks that every `_` in a number separates two digits
+    fn check_separators(text: &str, radix: u32) -> Result<(), LexicalErrorType> {
+        let chars = text.chars().collect::<Vec<_>>();
+        let is_digit = |index: Option<usize>| {
+            index
+                .and_then(|index| chars.get(index))
+                .is_some_and(|c| c.is_digit(radix))
+        };
+
+        for (index, &c) in chars.iter().enumerate() {
+            if c == '_' && !(is_digit(index.checked_sub(1)) && is_digit(Some(index + 1))) {
+                return Err(LexicalErrorType::MalformedNumber(
+                    "'_' must be between two digits".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Consumes the current character. Offsets move by the character's UTF-8
+    /// length, so they can always be used to slice `source`.
     fn advance(&mut self) -> char {
-        let curr_char = self.source.chars().nth(self.current).unwrap();
-        self.current += 1;
+        let curr_char = self.peek().expect("advanced past the end of the source");
+        self.current += curr_char.len_utf8();
+        self.column += 1;
 
         curr_char
     }
 
     fn advance_if_equal(&mut self, expected: char) -> bool {
-        if self.current >= self.source.len() {
-            return false;
-        }
-
-        if self.source.chars().nth(self.current).unwrap() == expected {
-            self.current += 1;
+        if self.current_char_matches(expected) {
+            self.advance();
             true
         } else {
             false
         }
     }
 
-    fn current_char_matches(&mut self, expected: char) -> bool {
-        if self.current >= self.source.len()
-            || self.source.chars().nth(self.current) != Some(expected)
-        {
-            false
-        } else {
-            true
+    /// Remembers where the next lexeme starts
+    fn start_lexeme(&mut self) {
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column;
+    }
+
+    /// Moves to the next line after a newline character was consumed
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.column = 1;
+    }
+
+    /// The text of the lexeme being processed, up to the current character
+    fn lexeme(&self) -> &'src str {
+        &self.source[self.start..self.current]
+    }
+
+    /// The span of the lexeme being processed, up to the current character
+    fn span(&self) -> Span {
+        Span::new(self.start, self.current, self.start_line, self.start_column)
+    }
+
+    fn current_char_matches(&self, expected: char) -> bool {
+        self.peek() == Some(expected)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.current..].chars().next()
+    }
+
+    fn peek_next(&self) -> Option<char> {
+        let mut chars = self.source[self.current..].chars();
+        chars.next();
+        chars.next()
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.current >= self.source.len()
+    }
+}
+
+impl<'src> Iterator for Lexer<'src> {
+    type Item = Result<Token<'src>, LexicalError>;
+
+    /// Scans up to the next token or error. The last token is always `Eof`.
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            if self.finished {
+                return None;
+            } else if self.is_at_end() {
+                self.finish();
+            } else {
+                self.scan_token();
+            }
         }
+
+        self.pending.pop_front()
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::lexical_analysis::span::Span;
+    use crate::lexical_analysis::symbol::Symbol;
     use crate::lexical_analysis::token::{Literal, Token};
     use crate::lexical_analysis::token_type::TokenType;
 
@@ -324,17 +674,17 @@ mod test {
     fn test_single_character_tokens() {
         let source = "(){},.-+;*";
         let expected = vec![
-            Token::new(TokenType::LeftParen, "(".to_string(), None, 1),
-            Token::new(TokenType::RightParen, ")".to_string(), None, 1),
-            Token::new(TokenType::LeftBrace, "{".to_string(), None, 1),
-            Token::new(TokenType::RightBrace, "}".to_string(), None, 1),
-            Token::new(TokenType::Comma, ",".to_string(), None, 1),
-            Token::new(TokenType::Dot, ".".to_string(), None, 1),
-            Token::new(TokenType::Minus, "-".to_string(), None, 1),
-            Token::new(TokenType::Plus, "+".to_string(), None, 1),
-            Token::new(TokenType::Semicolon, ";".to_string(), None, 1),
-            Token::new(TokenType::Star, "*".to_string(), None, 1),
-            Token::new(TokenType::Eof, "".to_string(), None, 1),
+            Token::new(TokenType::LeftParen, "(", None, Span::new(0, 1, 1, 1)),
+            Token::new(TokenType::RightParen, ")", None, Span::new(1, 2, 1, 2)),
+            Token::new(TokenType::LeftBrace, "{", None, Span::new(2, 3, 1, 3)),
+            Token::new(TokenType::RightBrace, "}", None, Span::new(3, 4, 1, 4)),
+            Token::new(TokenType::Comma, ",", None, Span::new(4, 5, 1, 5)),
+            Token::new(TokenType::Dot, ".", None, Span::new(5, 6, 1, 6)),
+            Token::new(TokenType::Minus, "-", None, Span::new(6, 7, 1, 7)),
+            Token::new(TokenType::Plus, "+", None, Span::new(7, 8, 1, 8)),
+            Token::new(TokenType::Semicolon, ";", None, Span::new(8, 9, 1, 9)),
+            Token::new(TokenType::Star, "*", None, Span::new(9, 10, 1, 10)),
+            Token::new(TokenType::Eof, "", None, Span::new(10, 10, 1, 11)),
         ];
         assert_tokens(source, expected);
     }
@@ -343,15 +693,20 @@ mod test {
     fn test_one_or_two_character_tokens() {
         let source = "! != = == > >= < <= //";
         let expected = vec![
-            Token::new(TokenType::Bang, "!".to_string(), None, 1),
-            Token::new(TokenType::BangEqual, "!=".to_string(), None, 1),
-            Token::new(TokenType::Equal, "=".to_string(), None, 1),
-            Token::new(TokenType::EqualEqual, "==".to_string(), None, 1),
-            Token::new(TokenType::Greater, ">".to_string(), None, 1),
-            Token::new(TokenType::GreaterEqual, ">=".to_string(), None, 1),
-            Token::new(TokenType::Less, "<".to_string(), None, 1),
-            Token::new(TokenType::LessEqual, "<=".to_string(), None, 1),
-            Token::new(TokenType::Eof, "".to_string(), None, 1),
+            Token::new(TokenType::Bang, "!", None, Span::new(0, 1, 1, 1)),
+            Token::new(TokenType::BangEqual, "!=", None, Span::new(2, 4, 1, 3)),
+            Token::new(TokenType::Equal, "=", None, Span::new(5, 6, 1, 6)),
+            Token::new(TokenType::EqualEqual, "==", None, Span::new(7, 9, 1, 8)),
+            Token::new(TokenType::Greater, ">", None, Span::new(10, 11, 1, 11)),
+            Token::new(
+                TokenType::GreaterEqual,
+                ">=",
+                None,
+                Span::new(12, 14, 1, 13),
+            ),
+            Token::new(TokenType::Less, "<", None, Span::new(15, 16, 1, 16)),
+            Token::new(TokenType::LessEqual, "<=", None, Span::new(17, 19, 1, 18)),
+            Token::new(TokenType::Eof, "", None, Span::new(22, 22, 1, 23)),
         ];
         assert_tokens(source, expected);
     }
@@ -364,17 +719,17 @@ string""#;
         let expected = vec![
             Token::new(
                 TokenType::String,
-                "\"Hello, World!\"".to_string(),
-                Some(Literal::String("Hello, World!".to_string())),
-                1,
+                "\"Hello, World!\"",
+                Some(Literal::String(Rc::from("Hello, World!"))),
+                Span::new(0, 15, 1, 1),
             ),
             Token::new(
                 TokenType::String,
-                "\"Multi\nline\nstring\"".to_string(),
-                Some(Literal::String("Multi\nline\nstring".to_string())),
-                3,
+                "\"Multi\nline\nstring\"",
+                Some(Literal::String(Rc::from("Multi\nline\nstring"))),
+                Span::new(16, 35, 1, 17),
             ),
-            Token::new(TokenType::Eof, "".to_string(), None, 3),
+            Token::new(TokenType::Eof, "", None, Span::new(35, 35, 3, 8)),
         ];
         assert_tokens(source, expected);
     }
@@ -385,23 +740,23 @@ string""#;
         let expected = vec![
             Token::new(
                 TokenType::Number,
-                "123".to_string(),
+                "123",
                 Some(Literal::Number(123.0)),
-                1,
+                Span::new(0, 3, 1, 1),
             ),
             Token::new(
                 TokenType::Number,
-                "123.456".to_string(),
+                "123.456",
                 Some(Literal::Number(123.456)),
-                1,
+                Span::new(4, 11, 1, 5),
             ),
             Token::new(
                 TokenType::Number,
-                "0.123".to_string(),
+                "0.123",
                 Some(Literal::Number(0.123)),
-                1,
+                Span::new(12, 17, 1, 13),
             ),
-            Token::new(TokenType::Eof, "".to_string(), None, 1),
+            Token::new(TokenType::Eof, "", None, Span::new(17, 17, 1, 18)),
         ];
         assert_tokens(source, expected);
     }
@@ -410,18 +765,325 @@ string""#;
     fn test_keywords_and_identifiers() {
         let source = "var number = 42;";
         let expected = vec![
-            Token::new(TokenType::Var, "var".to_string(), None, 1),
-            Token::new(TokenType::Identifier, "number".to_string(), None, 1),
-            Token::new(TokenType::Equal, "=".to_string(), None, 1),
+            Token::name(TokenType::Var, "var", Span::new(0, 3, 1, 1)),
+            Token::name(TokenType::Identifier, "number", Span::new(4, 10, 1, 5)),
+            Token::new(TokenType::Equal, "=", None, Span::new(11, 12, 1, 12)),
             Token::new(
                 TokenType::Number,
-                "42".to_string(),
+                "42",
                 Some(Literal::Number(42.0)),
-                1,
+                Span::new(13, 15, 1, 14),
+            ),
+            Token::new(TokenType::Semicolon, ";", None, Span::new(15, 16, 1, 16)),
+            Token::new(TokenType::Eof, "", None, Span::new(16, 16, 1, 17)),
+        ];
+        assert_tokens(source, expected);
+    }
+
+    #[test]
+    fn test_non_ascii_input() {
+        let source = "// größe ✓\nvar größe = \"naïve ☕\"; π";
+        let expected = vec![
+            Token::name(TokenType::Var, "var", Span::new(15, 18, 2, 1)),
+            Token::name(TokenType::Identifier, "größe", Span::new(19, 26, 2, 5)),
+            Token::new(TokenType::Equal, "=", None, Span::new(27, 28, 2, 11)),
+            Token::new(
+                TokenType::String,
+                "\"naïve ☕\"",
+                Some(Literal::String(Rc::from("naïve ☕"))),
+                Span::new(29, 41, 2, 13),
+            ),
+            Token::new(TokenType::Semicolon, ";", None, Span::new(41, 42, 2, 22)),
+            Token::name(TokenType::Identifier, "π", Span::new(43, 45, 2, 24)),
+            Token::new(TokenType::Eof, "", None, Span::new(45, 45, 2, 25)),
+        ];
+        assert_tokens(source, expected);
+    }
+
+    #[test]
+    fn test_spans_across_lines() {
+        let source = "var s = \"a\nb\";\n\tx";
+        let expected = vec![
+            Token::name(TokenType::Var, "var", Span::new(0, 3, 1, 1)),
+            Token::name(TokenType::Identifier, "s", Span::new(4, 5, 1, 5)),
+            Token::new(TokenType::Equal, "=", None, Span::new(6, 7, 1, 7)),
+            // A multi-line token is placed at its first character
+            Token::new(
+                TokenType::String,
+                "\"a\nb\"",
+                Some(Literal::String(Rc::from("a\nb"))),
+                Span::new(8, 13, 1, 9),
             ),
-            Token::new(TokenType::Semicolon, ";".to_string(), None, 1),
-            Token::new(TokenType::Eof, "".to_string(), None, 1),
+            Token::new(TokenType::Semicolon, ";", None, Span::new(13, 14, 2, 3)),
+            // A tab is a single column
+            Token::name(TokenType::Identifier, "x", Span::new(16, 17, 3, 2)),
+            Token::new(TokenType::Eof, "", None, Span::new(17, 17, 3, 3)),
         ];
         assert_tokens(source, expected);
     }
+
+    #[test]
+    fn test_error_spans() {
+        let (_, errors) = Lexer::new("print 1;\n  \"open").scan_tokens();
+        assert_eq!(errors[0].span(), Span::new(11, 16, 2, 3));
+
+        let (_, errors) = Lexer::new("a\n@ #").scan_tokens();
+        assert_eq!(
+            errors.iter().map(LexicalError::span).collect::<Vec<_>>(),
+            [Span::new(2, 3, 2, 1), Span::new(4, 5, 2, 3)]
+        );
+    }
+
+    #[test]
+    fn test_unexpected_non_ascii_character() {
+        let mut lexer = Lexer::new("1 € 2");
+        let (tokens, errors) = lexer.scan_tokens();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec!["[line 1] Error: Unexpected character: €"]
+        );
+        assert_eq!(errors[0].span(), Span::new(2, 5, 1, 3));
+    }
+
+    #[test]
+    fn test_escape_sequences() {
+        let mut lexer = Lexer::new(r#""a\tb\n\"c\" \\ \$ \u{e9}\u{1F600}""#);
+        let (tokens, errors) = lexer.scan_tokens();
+
+        assert!(errors.is_empty(), "Unexpected lexical errors: {:?}", errors);
+        assert_eq!(
+            tokens[0].literal(),
+            Some(&Literal::String(Rc::from("a\tb\n\"c\" \\ $ é😀")))
+        );
+    }
+
+    #[test]
+    fn test_invalid_escape_sequences() {
+        let mut lexer = Lexer::new("\"\\q\"\n\"\\u{110000} \\u{} \\u1\"");
+        let (tokens, errors) = lexer.scan_tokens();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec![
+                "[line 1] Error: Invalid escape sequence: \\q",
+                "[line 2] Error: Invalid unicode escape.",
+                "[line 2] Error: Invalid unicode escape.",
+                "[line 2] Error: Invalid unicode escape.",
+            ]
+        );
+        assert_eq!(errors[0].span(), Span::new(1, 3, 1, 2));
+        assert_eq!(errors[1].span(), Span::new(6, 16, 2, 2));
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let source = r#""a ${b + "${c}"} d" {}"#;
+        let mut lexer = Lexer::new(source);
+        let (tokens, errors) = lexer.scan_tokens();
+
+        assert!(errors.is_empty(), "Unexpected lexical errors: {:?}", errors);
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| token.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "INTERPOLATION \"a ${ a ",
+                "IDENTIFIER b null",
+                "PLUS + null",
+                "INTERPOLATION \"${ ",
+                "IDENTIFIER c null",
+                "STRING }\" ",
+                "STRING } d\"  d",
+                "LEFT_BRACE { null",
+                "RIGHT_BRACE } null",
+                "EOF  null",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_interpolation() {
+        for source in ["\"${a", "\"${ { } \"", "\"${a} b"] {
+            let mut lexer = Lexer::new(source);
+            let (_, errors) = lexer.scan_tokens();
+
+            assert_eq!(
+                errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+                vec!["[line 1] Error: Unterminated string."],
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_extended_number_literals() {
+        let mut lexer = Lexer::new("0xFF 0Xff_ff 0b1010 1e-9 2.5E+3 1_000_000 0.000_1 7e2");
+        let (tokens, errors) = lexer.scan_tokens();
+
+        assert!(errors.is_empty(), "Unexpected lexical errors: {:?}", errors);
+        assert_eq!(
+            tokens
+                .iter()
+                .filter_map(|token| token.literal().cloned())
+                .collect::<Vec<_>>(),
+            vec![
+                Literal::Number(255.0),
+                Literal::Number(65535.0),
+                Literal::Number(10.0),
+                Literal::Number(1e-9),
+                Literal::Number(2500.0),
+                Literal::Number(1_000_000.0),
+                Literal::Number(0.0001),
+                Literal::Number(700.0),
+            ]
+        );
+        assert_eq!(tokens[0].lexeme, "0xFF");
+    }
+
+    #[test]
+    fn test_malformed_numbers() {
+        let mut lexer = Lexer::new("0x 0b102 0xFG 1e 1e+ 1_ 1__0 0x_1 2 ");
+        let (tokens, errors) = lexer.scan_tokens();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec![
+                "[line 1] Error: Malformed number: hexadecimal number has no digits.",
+                "[line 1] Error: Malformed number: '2' is not a binary digit.",
+                "[line 1] Error: Malformed number: 'G' is not a hexadecimal digit.",
+                "[line 1] Error: Malformed number: exponent has no digits.",
+                "[line 1] Error: Malformed number: exponent has no digits.",
+                "[line 1] Error: Malformed number: '_' must be between two digits.",
+                "[line 1] Error: Malformed number: '_' must be between two digits.",
+                "[line 1] Error: Malformed number: '_' must be between two digits.",
+            ]
+        );
+        assert_eq!(errors[1].span(), Span::new(3, 8, 1, 4));
+    }
+
+    #[test]
+    fn test_block_comments() {
+        let source = "1 /* one\n/* nested\n*/ still */ 2 /**/ 3";
+        let mut lexer = Lexer::new(source);
+        let (tokens, errors) = lexer.scan_tokens();
+
+        assert!(errors.is_empty(), "Unexpected lexical errors: {:?}", errors);
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| (&*token.lexeme, token.span().line()))
+                .collect::<Vec<_>>(),
+            vec![("1", 1), ("2", 3), ("3", 3), ("", 3)]
+        );
+
+        let mut lexer = Lexer::new("1 /* a /* b */\n");
+        let (tokens, errors) = lexer.scan_tokens();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec!["[line 1] Error: Unterminated block comment."]
+        );
+        assert_eq!(errors[0].span(), Span::new(2, 15, 1, 3));
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let source = "/// Adds.\n///\n/// Twice.\nfun // not docs\n//// nor this\nvar x;";
+        let mut lexer = Lexer::new(source);
+        let (tokens, errors) = lexer.scan_tokens();
+
+        assert!(errors.is_empty(), "Unexpected lexical errors: {:?}", errors);
+        assert_eq!(
+            tokens.iter().map(|token| token.doc()).collect::<Vec<_>>(),
+            vec![Some(&Rc::from("Adds.\n\nTwice.")), None, None, None, None]
+        );
+    }
+
+    #[test]
+    fn test_tokens_are_streamed() {
+        let mut lexer = Lexer::new("1 @ \"\\q\\w\" 2");
+
+        let results = lexer
+            .by_ref()
+            .take(4)
+            .map(|result| match result {
+                Ok(token) => token.lexeme.to_string(),
+                Err(error) => error.to_string(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            vec![
+                "1",
+                "[line 1] Error: Unexpected character: @",
+                "[line 1] Error: Invalid escape sequence: \\q",
+                "[line 1] Error: Invalid escape sequence: \\w",
+            ]
+        );
+
+        let rest = lexer
+            .map(|result| result.unwrap().typ())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rest,
+            vec![TokenType::String, TokenType::Number, TokenType::Eof]
+        );
+    }
+
+    #[test]
+    fn test_scanning_stops_when_asked() {
+        let source = "var x = 1;\n".repeat(10_000);
+        let mut lexer = Lexer::new(&source);
+
+        let first = lexer.next().unwrap().unwrap();
+        assert_eq!(first.typ(), TokenType::Var);
+        assert_eq!(lexer.current, 3);
+    }
+
+    #[test]
+    fn test_scanning_from_an_offset() {
+        let source = "var a = 1;\nprint a;";
+        let (tokens, _) = Lexer::starting_at(source, 11).scan_tokens();
+
+        assert_eq!(tokens[0].typ(), TokenType::Print);
+        assert_eq!(tokens[0].span(), Span::new(11, 16, 2, 1));
+        assert_eq!(tokens[1].span(), Span::new(17, 18, 2, 7));
+    }
+
+    #[test]
+    fn test_lexemes_borrow_from_the_source() {
+        let source = String::from("print greeting + \"!\" + 42;");
+        let (tokens, _) = Lexer::new(&source).scan_tokens();
+
+        assert!(std::ptr::eq(&*tokens[1].lexeme, &source[6..14]));
+        assert!(std::ptr::eq(&*tokens[3].lexeme, &source[17..20]));
+
+        let owned: Vec<Token<'static>> = tokens.into_iter().map(Token::into_owned).collect();
+        drop(source);
+
+        assert_eq!(owned[1].lexeme, "greeting");
+        assert_eq!(owned[1].span(), Span::new(6, 14, 1, 7));
+        assert_eq!(owned[3].lexeme, "\"!\"");
+        assert_eq!(owned[5].lexeme, "42");
+    }
+
+    #[test]
+    fn test_scanning_literals_interns_nothing() {
+        let source = (0..1000)
+            .map(|i| format!("{} \"text {}\" + ", i, i))
+            .collect::<String>();
+        let interned = Symbol::count();
+
+        for result in Lexer::new(&source) {
+            assert!(result.is_ok());
+        }
+        assert_eq!(Symbol::count(), interned);
+    }
 }
@@ -0,0 +1,44 @@
+/// A region of the source code, used to point tokens and errors at the exact
+/// characters they came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    /// The byte offset of the first character.
+    start: usize,
+    /// The byte offset just past the last character.
+    end: usize,
+    /// The line of the first character, starting at 1.
+    line: usize,
+    /// The column of the first character within its line, counted in
+    /// characters and starting at 1.
+    column: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Self {
+        Self {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+
+    #[allow(unused)]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    #[allow(unused)]
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    #[allow(unused)]
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
@@ -0,0 +1,120 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+/// Names the runtime needs to look up itself, interned up front so that they
+/// are constants
+const PREDEFINED: [&str; 3] = ["init", "this", "super"];
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner::new());
+}
+
+/// A handle to an interned string. Two symbols are equal exactly when their
+/// text is, so comparing and hashing them never looks at the text.
+///
+/// Symbols are interned for the lifetime of the thread, so they are meant
+/// for names from the source rather than strings built at runtime. Each
+/// thread has its own interner, so symbols can not be sent to other threads.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32, PhantomData<*const ()>);
+
+impl Symbol {
+    pub const INIT: Symbol = Symbol(0, PhantomData);
+    pub const THIS: Symbol = Symbol(1, PhantomData);
+    pub const SUPER: Symbol = Symbol(2, PhantomData);
+
+    /// Returns the symbol for the given text, interning it if it is new
+    pub fn intern(text: &str) -> Self {
+        INTERNER.with(|interner| interner.borrow_mut().intern(text))
+    }
+
+    /// Returns the symbol for the given text if it has been interned, without
+    /// interning it otherwise
+    pub fn lookup(text: &str) -> Option<Self> {
+        INTERNER.with(|interner| interner.borrow().symbols.get(text).copied())
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.with(|interner| interner.borrow().strings[self.0 as usize])
+    }
+
+    /// How many strings the thread has interned so far
+    #[cfg(test)]
+    pub(crate) fn count() -> usize {
+        INTERNER.with(|interner| interner.borrow().strings.len())
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+/// Maps text to symbols and back. The text of every symbol is leaked, as
+/// symbols can be used for as long as the thread lives.
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    strings: Vec<&'static str>,
+}
+
+impl Interner {
+    fn new() -> Self {
+        let mut interner = Self {
+            symbols: HashMap::new(),
+            strings: Vec::new(),
+        };
+
+        for text in PREDEFINED {
+            interner.intern(text);
+        }
+
+        interner
+    }
+
+    fn intern(&mut self, text: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(text) {
+            return symbol;
+        }
+
+        let text: &'static str = Box::leak(text.into());
+        let symbol = Symbol(self.strings.len() as u32, PhantomData);
+        self.symbols.insert(text, symbol);
+        self.strings.push(text);
+        symbol
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_interning() {
+        let a = Symbol::intern("name");
+        assert_eq!(a, Symbol::intern("name"));
+        assert_ne!(a, Symbol::intern("other"));
+        assert_eq!(a.as_str(), "name");
+
+        assert_eq!(Symbol::intern("init"), Symbol::INIT);
+        assert_eq!(Symbol::intern("this"), Symbol::THIS);
+        assert_eq!(Symbol::intern("super"), Symbol::SUPER);
+    }
+
+    #[test]
+    fn test_lookup_interns_nothing() {
+        let count = Symbol::count();
+        assert_eq!(Symbol::lookup("never interned"), None);
+        assert_eq!(Symbol::count(), count);
+
+        let symbol = Symbol::intern("looked up");
+        assert_eq!(Symbol::lookup("looked up"), Some(symbol));
+    }
+}
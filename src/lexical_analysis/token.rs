@@ -1,9 +1,14 @@
+use crate::lexical_analysis::span::Span;
+use crate::lexical_analysis::symbol::Symbol;
 use crate::lexical_analysis::token_type::TokenType;
+use std::borrow::Cow;
+use std::rc::Rc;
 
 #[allow(unused)]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
-    String(String),
+    /// The text of a string, shared by every value the literal evaluates to.
+    String(Rc<str>),
     Number(f64),
     Boolean(bool),
     Nil,
@@ -26,26 +31,94 @@ impl std::fmt::Display for Literal {
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub struct Token {
+/// A token borrowing its lexeme from the source it was scanned from. Syntax
+/// trees outlive the source, so they hold tokens made `'static` by
+/// `into_owned`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token<'src> {
     typ: TokenType,
-    pub lexeme: String,
+    pub lexeme: Cow<'src, str>,
+    /// The interned lexeme of identifiers and keywords, which is what names
+    /// are looked up by. Other lexemes are not interned, as interned text is
+    /// never freed.
+    symbol: Option<Symbol>,
     literal: Option<Literal>,
-    line: usize,
+    span: Span,
+    /// The text of the `///` doc comments right before the token, if any.
+    doc: Option<Rc<str>>,
 }
 
-impl Token {
-    pub fn new(typ: TokenType, lexeme: String, literal: Option<Literal>, line: usize) -> Self {
+impl<'src> Token<'src> {
+    pub fn new(typ: TokenType, lexeme: &'src str, literal: Option<Literal>, span: Span) -> Self {
         Self {
             typ,
-            lexeme,
+            lexeme: Cow::Borrowed(lexeme),
+            symbol: None,
             literal,
-            line,
+            span,
+            doc: None,
+        }
+    }
+
+    /// Creates an identifier or keyword token, whose lexeme is interned
+    pub fn name(typ: TokenType, lexeme: &'src str, span: Span) -> Self {
+        Self {
+            symbol: Some(Symbol::intern(lexeme)),
+            ..Self::new(typ, lexeme, None, span)
+        }
+    }
+
+    pub fn with_doc(mut self, doc: Rc<str>) -> Self {
+        self.doc = Some(doc);
+        self
+    }
+
+    /// Detaches the token from the source. Names borrow the interned copy
+    /// of their lexeme, while other lexemes are copied.
+    pub fn into_owned(self) -> Token<'static> {
+        let lexeme = match self.symbol {
+            Some(symbol) => Cow::Borrowed(symbol.as_str()),
+            None => Cow::Owned(self.lexeme.into_owned()),
+        };
+
+        Token {
+            typ: self.typ,
+            lexeme,
+            symbol: self.symbol,
+            literal: self.literal,
+            span: self.span,
+            doc: self.doc,
         }
     }
+
+    pub fn typ(&self) -> TokenType {
+        self.typ
+    }
+
+    /// The interned name of an identifier or keyword
+    ///
+    /// # Panics
+    ///
+    /// If the token is not a name.
+    pub fn symbol(&self) -> Symbol {
+        self.symbol
+            .expect("only identifiers and keywords are interned")
+    }
+
+    pub fn literal(&self) -> Option<&Literal> {
+        self.literal.as_ref()
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn doc(&self) -> Option<&Rc<str>> {
+        self.doc.as_ref()
+    }
 }
 
-impl std::fmt::Display for Token {
+impl std::fmt::Display for Token<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
@@ -1,4 +1,4 @@
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenType {
     // Single-character tokens.
     LeftParen,
@@ -26,6 +26,8 @@ pub enum TokenType {
     // Literals.
     Identifier,
     String,
+    /// The part of a string before an interpolated `${expression}`.
+    Interpolation,
     Number,
 
     // Keywords.
@@ -97,6 +99,7 @@ impl std::fmt::Display for TokenType {
             TokenType::LessEqual => write!(f, "LESS_EQUAL"),
             TokenType::Identifier => write!(f, "IDENTIFIER"),
             TokenType::String => write!(f, "STRING"),
+            TokenType::Interpolation => write!(f, "INTERPOLATION"),
             TokenType::Number => write!(f, "NUMBER"),
             TokenType::And => write!(f, "AND"),
             TokenType::Class => write!(f, "CLASS"),
@@ -0,0 +1,30 @@
+//! An interpreter for the Lox programming language from
+//! [Crafting Interpreters](https://craftinginterpreters.com/), with a
+//! tree-walk interpreter and a bytecode virtual machine.
+//!
+//! Most programs only need [`Lox`], which runs source code and exchanges
+//! values with it:
+//!
+//! ```
+//! use lox_interpreter::{Lox, Value};
+//!
+//! let mut lox = Lox::new();
+//! lox.set_global("base", Value::Number(40.0));
+//! assert_eq!(lox.eval("base + 2").unwrap(), Value::Number(42.0));
+//! ```
+//!
+//! The modules expose each stage of the pipeline on its own, for tools
+//! which need the tokens, the syntax tree or the bytecode.
+
+pub mod bytecode;
+pub mod diagnostics;
+pub mod evaluation;
+pub mod lexical_analysis;
+mod lox;
+pub mod semantic_analysis;
+pub mod syntax_analysis;
+
+pub use evaluation::error::RuntimeErrorType;
+pub use evaluation::host::{Ctx, HostClass, HostResult};
+pub use evaluation::value::Value;
+pub use lox::{Lox, LoxError, Program};
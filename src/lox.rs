@@ -0,0 +1,549 @@
+use crate::diagnostics::Diagnostic;
+use crate::evaluation::error::RuntimeError;
+use crate::evaluation::host::{Ctx, HostClass, HostFunction, HostResult};
+use crate::evaluation::interpreter::Interpreter;
+use crate::evaluation::value::Value;
+use crate::lexical_analysis::error::LexicalError;
+use crate::lexical_analysis::lexer::Lexer;
+use crate::semantic_analysis::resolver::Resolver;
+use crate::syntax_analysis::expression::ExprId;
+use crate::syntax_analysis::parser::Parser;
+use crate::syntax_analysis::statement::Stmt;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+use std::{fs, io};
+
+/// Why running source code failed
+#[derive(Debug)]
+pub enum LoxError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The source could not be scanned, parsed or resolved, so none of it
+    /// was run.
+    Compile(Vec<Diagnostic>),
+    /// The program failed while executing.
+    Runtime(RuntimeError),
+    /// The program called `exit` with the given code.
+    Exit(i32),
+}
+
+impl LoxError {
+    /// The errors in the form needed to render them against the source
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            LoxError::Compile(diagnostics) => diagnostics.clone(),
+            LoxError::Runtime(error) => vec![Diagnostic::from(error)],
+            LoxError::Io(_) | LoxError::Exit(_) => Vec::new(),
+        }
+    }
+}
+
+impl std::fmt::Display for LoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoxError::Io(error) => write!(f, "{}", error),
+            LoxError::Compile(diagnostics) => {
+                let messages = diagnostics.iter().map(Diagnostic::to_string);
+                write!(f, "{}", messages.collect::<Vec<_>>().join("\n"))
+            }
+            LoxError::Runtime(error) => write!(f, "{}", error),
+            LoxError::Exit(code) => write!(f, "Exited with code {}.", code),
+        }
+    }
+}
+
+impl std::error::Error for LoxError {}
+
+impl From<io::Error> for LoxError {
+    fn from(error: io::Error) -> Self {
+        LoxError::Io(error)
+    }
+}
+
+impl From<RuntimeError> for LoxError {
+    fn from(error: RuntimeError) -> Self {
+        match error.exit_code() {
+            Some(code) => LoxError::Exit(code),
+            None => LoxError::Runtime(error),
+        }
+    }
+}
+
+/// Source code which was parsed and resolved without errors, ready to run
+#[derive(Debug)]
+pub struct Program {
+    pub statements: Vec<Stmt>,
+    /// How many scopes up each local variable reference has to look.
+    locals: HashMap<ExprId, usize>,
+}
+
+impl Program {
+    pub fn compile(source: &str) -> Result<Self, LoxError> {
+        let statements = Self::parse(source, 0, false)?;
+        Self::resolve(statements)
+    }
+
+    /// Compiles source code the way it is typed at a prompt, where a lone
+    /// expression may leave out its semicolon
+    pub fn compile_interactive(source: &str) -> Result<Self, LoxError> {
+        Self::compile_interactive_at(source, 0)
+    }
+
+    /// Compiles the input starting at the given byte offset of a session's
+    /// source, which holds the earlier inputs before it. Functions from
+    /// earlier inputs keep their spans, so all errors point into the same
+    /// source.
+    pub fn compile_interactive_at(source: &str, offset: usize) -> Result<Self, LoxError> {
+        let statements = Self::parse(source, offset, true)?;
+        Self::resolve(statements)
+    }
+
+    /// Compiles source code made up of a single expression, which running
+    /// the program evaluates to
+    pub fn compile_expression(source: &str) -> Result<Self, LoxError> {
+        let mut lexical_errors = Vec::new();
+        let result = Parser::new(Lexer::new(source).tokens(&mut lexical_errors)).parse_expression();
+        Self::check_lexical_errors(&lexical_errors)?;
+
+        let expr = result.map_err(|error| LoxError::Compile(vec![Diagnostic::from(&error)]))?;
+        Self::resolve(vec![Stmt::Expression { expr }])
+    }
+
+    /// Parses the source as it is scanned, so that its tokens are never all
+    /// held at once
+    fn parse(source: &str, offset: usize, interactive: bool) -> Result<Vec<Stmt>, LoxError> {
+        let mut lexical_errors = Vec::new();
+
+        if interactive {
+            let tokens = Lexer::starting_at(source, offset).tokens(&mut lexical_errors);
+            let result = Parser::new(tokens).parse_expression();
+            if let Ok(expr) = result {
+                if lexical_errors.is_empty() {
+                    return Ok(vec![Stmt::Expression { expr }]);
+                }
+            }
+
+            // The expression may have stopped short of later errors, so the
+            // source is scanned again as a program
+            lexical_errors.clear();
+        }
+
+        let tokens = Lexer::starting_at(source, offset).tokens(&mut lexical_errors);
+        let (statements, parse_errors) = Parser::new(tokens).parse();
+        Self::check_lexical_errors(&lexical_errors)?;
+
+        if !parse_errors.is_empty() {
+            return Err(LoxError::Compile(
+                parse_errors.iter().map(Diagnostic::from).collect(),
+            ));
+        }
+
+        Ok(statements)
+    }
+
+    /// Lexical errors are reported instead of syntax errors, which may only
+    /// follow from the tokens that are missing
+    fn check_lexical_errors(errors: &[LexicalError]) -> Result<(), LoxError> {
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(LoxError::Compile(
+                errors.iter().map(Diagnostic::from).collect(),
+            ))
+        }
+    }
+
+    fn resolve(statements: Vec<Stmt>) -> Result<Self, LoxError> {
+        let (locals, resolve_errors) = Resolver::new().resolve(&statements);
+        if !resolve_errors.is_empty() {
+            return Err(LoxError::Compile(
+                resolve_errors.iter().map(Diagnostic::from).collect(),
+            ));
+        }
+
+        Ok(Self { statements, locals })
+    }
+}
+
+/// An interpreter session. Globals persist from one call to the next, so a
+/// program can be run in pieces, and the host can exchange values with it
+/// through global variables.
+pub struct Lox {
+    interpreter: Interpreter,
+}
+
+impl Lox {
+    pub fn new() -> Self {
+        Self {
+            interpreter: Interpreter::new(),
+        }
+    }
+
+    /// Runs source code, returning the value of its last statement if that
+    /// is an expression, and `nil` otherwise. A lone expression may leave
+    /// out its semicolon.
+    pub fn eval(&mut self, source: &str) -> Result<Value, LoxError> {
+        let program = Program::compile_interactive(source)?;
+        self.execute(program)
+    }
+
+    /// Reads and runs a script
+    pub fn run_file(&mut self, path: impl AsRef<Path>) -> Result<(), LoxError> {
+        let source = fs::read_to_string(path)?;
+        let program = Program::compile(&source)?;
+        self.execute(program)?;
+        Ok(())
+    }
+
+    /// Runs a compiled program, returning the value of its last statement
+    /// if that is an expression
+    pub fn execute(&mut self, program: Program) -> Result<Value, LoxError> {
+        self.interpreter.resolve(program.locals);
+        Ok(self.interpreter.interpret(&program.statements)?)
+    }
+
+    /// Defines a global variable, replacing any previous one of that name
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.interpreter.define_global(name, value);
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.interpreter.global(name)
+    }
+
+    /// Makes a Rust closure available to programs as a global function
+    pub fn define_function(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&mut Ctx, &[Value]) -> HostResult + 'static,
+    ) {
+        let function = HostFunction::new(name, arity, function);
+        self.set_global(name, Value::HostFunction(Rc::new(function)));
+    }
+
+    /// Makes a host class available to programs as a global. The class is
+    /// handed back for wrapping objects of the host as its instances.
+    pub fn define_class(&mut self, class: HostClass) -> Rc<HostClass> {
+        let class = Rc::new(class);
+        self.set_global(&class.name.clone(), Value::HostClass(Rc::clone(&class)));
+        class
+    }
+
+    /// Sends the output of `print` to the given writer instead of stdout
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.interpreter.set_output(Box::new(output));
+    }
+
+    /// Makes every allocation collect garbage first, to flush out objects
+    /// which are freed too early
+    pub fn set_gc_stress(&mut self, stress: bool) {
+        self.interpreter.set_gc_stress(stress);
+    }
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::diagnostics::Renderer;
+    use crate::evaluation::error::RuntimeErrorType;
+    use crate::evaluation::native::Arguments;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// A writer whose output can still be read after handing it over
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A writer whose reader has gone away
+    struct ClosedPipe;
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_eval() {
+        let mut lox = Lox::new();
+
+        assert_eq!(lox.eval("1 + 2").unwrap(), Value::Number(3.0));
+        assert_eq!(lox.eval("var a = 1;").unwrap(), Value::Nil);
+        assert_eq!(lox.eval("a = a + 1; a * 10;").unwrap(), Value::Number(20.0));
+        assert_eq!(
+            lox.eval("fun f() { return a; } f();").unwrap(),
+            Value::Number(2.0)
+        );
+    }
+
+    #[test]
+    fn test_compile_expression() {
+        let program = Program::compile_expression("\"a\" + \"b\"").unwrap();
+        assert_eq!(
+            Lox::new().execute(program).unwrap(),
+            Value::String(Rc::from("ab"))
+        );
+
+        assert!(matches!(
+            Program::compile_expression("print 1;"),
+            Err(LoxError::Compile(_))
+        ));
+    }
+
+    #[test]
+    fn test_lexical_errors_come_before_syntax_errors() {
+        let error = Program::compile("print 1 @;\nvar;\nprint #;").unwrap_err();
+        let messages = error
+            .diagnostics()
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            [
+                "error[E001]: Unexpected character: @ at 1:9",
+                "error[E001]: Unexpected character: # at 3:7",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_globals() {
+        let mut lox = Lox::new();
+        lox.set_global("width", Value::Number(3.0));
+        lox.eval("var area = width * width;").unwrap();
+
+        assert_eq!(lox.get_global("area"), Some(Value::Number(9.0)));
+        assert_eq!(lox.get_global("height"), None);
+        assert_eq!(lox.get_global("never mentioned"), None);
+    }
+
+    #[test]
+    fn test_output() {
+        let buffer = SharedBuffer::default();
+        let mut lox = Lox::new();
+        lox.set_output(buffer.clone());
+
+        lox.eval("print \"one\"; print 2;").unwrap();
+
+        assert_eq!(String::from_utf8(buffer.0.take()).unwrap(), "one\n2\n");
+    }
+
+    #[test]
+    fn test_output_errors() {
+        let mut lox = Lox::new();
+        lox.set_output(ClosedPipe);
+
+        let error = lox.eval("var a = 1;\nprint a;\na = 2;").unwrap_err();
+        let LoxError::Runtime(error) = error else {
+            panic!("Expected a runtime error, got {:?}", error);
+        };
+        assert_eq!(
+            error.typ(),
+            &RuntimeErrorType::Output(io::ErrorKind::BrokenPipe)
+        );
+        assert_eq!(error.span().line(), 2);
+        assert_eq!(lox.get_global("a"), Some(Value::Number(1.0)));
+    }
+
+    #[test]
+    fn test_host_functions() {
+        let buffer = SharedBuffer::default();
+        let mut lox = Lox::new();
+        lox.set_output(buffer.clone());
+
+        lox.define_function("double", 1, |_, arguments| {
+            Ok(Value::Number(arguments.number(0)? * 2.0))
+        });
+        lox.define_function("log", 1, |ctx, arguments| {
+            writeln!(ctx.output(), "[log] {}", arguments[0]).expect("Could not write output!");
+            Ok(Value::Nil)
+        });
+        lox.define_function("twice", 1, |ctx, arguments| {
+            let once = ctx.call(&arguments[0], vec![Value::Number(1.0)])?;
+            ctx.call(&arguments[0], vec![once])
+        });
+
+        assert_eq!(lox.eval("double(21)").unwrap(), Value::Number(42.0));
+        assert_eq!(
+            lox.eval("fun inc(n) { return n + 1; } twice(inc);")
+                .unwrap(),
+            Value::Number(3.0)
+        );
+        lox.eval("log(type(log));").unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.0.take()).unwrap(),
+            "[log] function\n"
+        );
+
+        let error = lox.eval("double(\"a\");").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Expected a number as argument 1 but got a string.\n[line 1]"
+        );
+        assert!(matches!(lox.eval("double();"), Err(LoxError::Runtime(_))));
+
+        // Errors in callbacks point into the callback
+        let error = lox
+            .eval("fun fail(n) {\n  return -\"a\";\n}\ntwice(fail);")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Operand must be a number.\n[line 2]");
+        assert_eq!(
+            error.diagnostics()[0].to_string(),
+            "error[E300]: Operand must be a number. at 2:10"
+        );
+        let LoxError::Runtime(error) = error else {
+            panic!("Expected a runtime error, got {:?}", error);
+        };
+        assert_eq!(
+            error
+                .trace()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["[line 2] in fail()", "[line 4] in script"]
+        );
+    }
+
+    #[derive(Default)]
+    struct Config {
+        verbose: bool,
+        name: String,
+    }
+
+    fn config_class() -> HostClass {
+        HostClass::new::<Config>("Config")
+            .constructor(1, |_, arguments| {
+                Ok(Config {
+                    verbose: false,
+                    name: arguments.string(0)?.to_string(),
+                })
+            })
+            .getter("verbose", |config| Value::Boolean(config.verbose))
+            .setter("verbose", |config, value| {
+                config.verbose = value.is_truthy();
+                Ok(())
+            })
+            .getter("name", |config| {
+                Value::String(Rc::from(config.name.as_str()))
+            })
+            .method("describe", 0, |config, _, _| {
+                let description = format!("{} (verbose: {})", config.name, config.verbose);
+                Ok(Value::String(Rc::from(description)))
+            })
+            .method("with", 1, |_, ctx, arguments| {
+                ctx.call(&arguments[0], Vec::new())
+            })
+            .build()
+    }
+
+    #[test]
+    fn test_host_classes() {
+        let mut lox = Lox::new();
+        let class = lox.define_class(config_class());
+
+        let config = Rc::new(RefCell::new(Config {
+            verbose: false,
+            name: "app".to_string(),
+        }));
+        lox.set_global("config", class.instance(Rc::clone(&config)));
+
+        lox.eval("config.verbose = true;").unwrap();
+        assert!(config.borrow().verbose);
+        assert_eq!(
+            lox.eval("config.describe()").unwrap(),
+            Value::String(Rc::from("app (verbose: true)"))
+        );
+
+        lox.eval("var other = Config(\"other\");").unwrap();
+        assert_eq!(
+            lox.eval("other.name + \" \" + type(other) + \" \" + str(Config)")
+                .unwrap(),
+            Value::String(Rc::from("other instance Config"))
+        );
+        assert_eq!(lox.eval("config == config").unwrap(), Value::Boolean(true));
+        assert_eq!(lox.eval("config == other").unwrap(), Value::Boolean(false));
+
+        let mut message = |source: &str| lox.eval(source).unwrap_err().to_string();
+        assert_eq!(
+            message("config.name = \"x\";"),
+            "Can't assign to read-only property 'name'.\n[line 1]"
+        );
+        assert_eq!(
+            message("config.missing;"),
+            "Undefined property 'missing'.\n[line 1]"
+        );
+        assert_eq!(
+            message("fun again() { config.describe(); } config.with(again);"),
+            "This 'Config' object is already in use.\n[line 1]"
+        );
+    }
+
+    #[test]
+    fn test_host_classes_without_constructor() {
+        let mut lox = Lox::new();
+        lox.define_class(HostClass::new::<Config>("Logger").build());
+
+        assert_eq!(
+            lox.eval("Logger();").unwrap_err().to_string(),
+            "Can't create instances of 'Logger'.\n[line 1]"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut lox = Lox::new();
+
+        assert!(matches!(lox.eval("print ;"), Err(LoxError::Compile(_))));
+        assert!(matches!(lox.eval("-\"a\""), Err(LoxError::Runtime(_))));
+        assert!(matches!(lox.eval("exit(3);"), Err(LoxError::Exit(3))));
+        assert!(matches!(
+            lox.run_file("does/not/exist.lox"),
+            Err(LoxError::Io(_))
+        ));
+
+        let error = lox.eval("var x = ;").unwrap_err();
+        assert_eq!(error.diagnostics().len(), 1);
+    }
+
+    #[test]
+    fn test_interactive_inputs_share_a_source() {
+        let mut lox = Lox::new();
+        let mut session = String::from("fun f() { var long_name = 1; return -\"a\"; }\n");
+        let program = Program::compile_interactive_at(&session, 0).unwrap();
+        lox.execute(program).unwrap();
+
+        let offset = session.len();
+        session.push_str("f();\n");
+        let program = Program::compile_interactive_at(&session, offset).unwrap();
+        let error = lox.execute(program).unwrap_err();
+
+        let rendered = Renderer::new(&session, None, false).render(&error.diagnostics()[0]);
+        assert!(rendered.contains("1 | fun f() { var long_name = 1; return -\"a\"; }"));
+        assert!(rendered.contains("[line 2] in script"));
+    }
+}
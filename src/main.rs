@@ -1,42 +1,463 @@
-mod lexical_analysis;
-
-use lexical_analysis::lexer::Lexer;
-use std::io::{stdin, BufRead};
+use lox_interpreter::bytecode::compiler::Compiler;
+use lox_interpreter::bytecode::disassembler::disassemble;
+use lox_interpreter::bytecode::vm::Vm;
+use lox_interpreter::diagnostics::{Diagnostic, Renderer};
+use lox_interpreter::evaluation::error::{RuntimeError, RuntimeErrorType};
+use lox_interpreter::lexical_analysis::error::LexicalErrorType;
+use lox_interpreter::lexical_analysis::lexer::Lexer;
+use lox_interpreter::lexical_analysis::token::Token;
+use lox_interpreter::lexical_analysis::token_type::TokenType;
+use lox_interpreter::syntax_analysis::parser::Parser;
+use lox_interpreter::syntax_analysis::statement::Stmt;
+use lox_interpreter::{Lox, LoxError, Program};
+use std::io::{self, stderr, stdin, stdout, ErrorKind, IsTerminal, Write};
+use std::process::exit;
 use std::{env, fs};
 
+const USAGE: &str =
+    "Usage: lox_interpreter [--vm] [--dump-bytecode] [--gc-stress] [tokenize | parse | evaluate | run] [script]";
+
+/// Why running a command failed
+enum RunError {
+    /// The command line arguments were not understood.
+    Usage,
+    /// The script could not be read.
+    Input,
+    /// The output could not be written.
+    Output,
+    /// The source could not be scanned, parsed or resolved.
+    Compile,
+    /// The program failed while executing.
+    Runtime,
+    /// The program called `exit` with the given code.
+    Exit(i32),
+}
+
+impl RunError {
+    /// The conventional exit code for this kind of failure, from sysexits.h
+    fn exit_code(&self) -> i32 {
+        match self {
+            RunError::Usage => 64,
+            RunError::Compile => 65,
+            RunError::Input => 66,
+            RunError::Output => 74,
+            RunError::Runtime => 70,
+            RunError::Exit(code) => *code,
+        }
+    }
+}
+
+/// The command line flags
+#[derive(Default)]
+struct Options {
+    /// Whether to run programs on the bytecode VM instead of the tree walker.
+    vm: bool,
+    /// Whether to print the bytecode of programs before running them.
+    dump_bytecode: bool,
+    /// Whether to collect garbage on every allocation.
+    gc_stress: bool,
+}
+
+impl Options {
+    fn parse(flags: &[&str]) -> Result<Self, RunError> {
+        let mut options = Options::default();
+
+        for &flag in flags {
+            match flag {
+                "--vm" => options.vm = true,
+                // Only the VM runs bytecode
+                "--dump-bytecode" => {
+                    options.vm = true;
+                    options.dump_bytecode = true;
+                }
+                "--gc-stress" => options.gc_stress = true,
+                _ => {
+                    eprintln!("Unknown option '{}'\n{}", flag, USAGE);
+                    return Err(RunError::Usage);
+                }
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// The engine which executes programs, kept alive across REPL inputs
+enum Engine {
+    TreeWalker(Lox),
+    Vm { vm: Vm, dump_bytecode: bool },
+}
+
+impl Engine {
+    fn new(options: &Options) -> Self {
+        if options.vm {
+            let mut vm = Vm::new();
+            vm.set_gc_stress(options.gc_stress);
+
+            Engine::Vm {
+                vm,
+                dump_bytecode: options.dump_bytecode,
+            }
+        } else {
+            let mut lox = Lox::new();
+            lox.set_gc_stress(options.gc_stress);
+
+            Engine::TreeWalker(lox)
+        }
+    }
+}
+
 fn main() {
-    let args = env::args().collect::<Vec<String>>();
-    match args.len() {
-        1 => run_repl(),
-        2 => run_file(&args[1]),
-        _ => println!("Usage: lox_interpreter [script]"),
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let (flags, args): (Vec<&str>, Vec<&str>) = args
+        .iter()
+        .map(String::as_str)
+        .partition(|arg| arg.starts_with("--"));
+
+    let options = Options::parse(&flags).unwrap_or_else(|error| exit(error.exit_code()));
+
+    if let Err(error) = run_command(&args, &options) {
+        exit(error.exit_code());
+    }
+}
+
+/// Runs the command given by the arguments left after the flags
+fn run_command(args: &[&str], options: &Options) -> Result<(), RunError> {
+    match args[..] {
+        [] => {
+            run_repl(Engine::new(options));
+            Ok(())
+        }
+        ["tokenize", path] => read_file(path).and_then(|source| tokenize(&source, path)),
+        ["parse", path] => read_file(path).and_then(|source| parse(&source, path)),
+        ["evaluate", path] => read_file(path).and_then(|source| evaluate(&source, path)),
+        // A command without a script is not a script named like the command
+        ["tokenize" | "parse" | "evaluate" | "run"] => {
+            eprintln!("{}", USAGE);
+            Err(RunError::Usage)
+        }
+        ["run", path] | [path] => {
+            read_file(path).and_then(|source| run(&source, Some(path), Engine::new(options)))
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            Err(RunError::Usage)
+        }
     }
 }
 
-fn run_file(path: &str) {
-    let read = fs::read(path).expect("Could not read file!");
-    let result = String::from_utf8(read).expect("Please enter a UTF-8 file!");
-    run(&result);
+fn read_file(path: &str) -> Result<String, RunError> {
+    fs::read_to_string(path).map_err(|error| {
+        eprintln!("Could not read '{}': {}", path, error);
+        RunError::Input
+    })
 }
 
-fn run_repl() {
-    println!(">");
-    let handle = stdin().lock();
+/// Reads and runs input until the end of stdin. All inputs share one
+/// engine, so declarations stay around for later inputs, and one source,
+/// which errors in functions from earlier inputs are rendered against.
+fn run_repl(mut engine: Engine) {
+    let mut session = String::new();
+    let mut input = String::new();
+
+    loop {
+        print!("{}", if input.is_empty() { "> " } else { "... " });
+        stdout().flush().expect("Could not write prompt!");
+
+        // Stdin is not kept locked, as programs may read from it too
+        let mut line = String::new();
+        if stdin().read_line(&mut line).expect("Could not read line!") == 0 {
+            println!();
+            break;
+        }
+
+        input.push_str(line.trim_end_matches(['\n', '\r']));
+        input.push('\n');
 
-    for line in handle.lines() {
-        run(&line.expect("Could not read line!"));
+        if is_input_complete(&input) {
+            let offset = session.len();
+            session.push_str(&input);
+            input.clear();
+
+            // Errors have already been reported, and should not end the session
+            if let Err(RunError::Exit(code)) = run_input(&mut engine, &session, offset) {
+                exit(code);
+            }
+        }
     }
 }
 
-fn run(source: &str) {
+/// Whether the input so far can be run, or is still missing closing
+/// brackets or the end of a string or comment and needs more lines
+fn is_input_complete(input: &str) -> bool {
+    let mut lexer = Lexer::new(input);
+    let (tokens, errors) = lexer.scan_tokens();
+
+    if errors.iter().any(|error| {
+        matches!(
+            error.typ(),
+            LexicalErrorType::UnterminatedString | LexicalErrorType::UnterminatedComment
+        )
+    }) {
+        return false;
+    }
+
+    let depth = tokens.iter().fold(0, |depth, token| match token.typ() {
+        TokenType::LeftParen | TokenType::LeftBrace => depth + 1,
+        TokenType::RightParen | TokenType::RightBrace => depth - 1,
+        _ => depth,
+    });
+
+    depth <= 0
+}
+
+/// Runs the REPL input starting at the given offset of the session's source.
+/// The values of bare expressions are printed, and a lone expression may
+/// leave out its semicolon.
+fn run_input(engine: &mut Engine, session: &str, offset: usize) -> Result<(), RunError> {
+    let renderer = renderer(session, None);
+    let mut program = Program::compile_interactive_at(session, offset)
+        .map_err(|error| lox_error(error, &renderer))?;
+
+    program.statements = program
+        .statements
+        .into_iter()
+        .map(|stmt| match stmt {
+            Stmt::Expression { expr } => Stmt::Print { expr },
+            stmt => stmt,
+        })
+        .collect();
+
+    execute(engine, program, &renderer)
+}
+
+/// Prints every token of the source, in the format of the `Token` display.
+/// Tokens are printed as they are scanned, so large sources are streamed.
+fn tokenize(source: &str, path: &str) -> Result<(), RunError> {
+    let renderer = renderer(source, Some(path));
+    let mut output = stdout().lock();
+    let mut failed = false;
+
+    for result in Lexer::new(source) {
+        match result {
+            Ok(token) => match writeln!(output, "{}", token) {
+                Ok(()) => {}
+                // Stop early once the reader goes away, such as `head`
+                // having seen enough
+                Err(error) if error.kind() == ErrorKind::BrokenPipe => break,
+                Err(error) => return Err(output_error(error)),
+            },
+            Err(error) => {
+                report(&renderer, Diagnostic::from(&error));
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        Err(RunError::Compile)
+    } else {
+        Ok(())
+    }
+}
+
+/// Prints the syntax tree of the source. A source made up of one bare
+/// expression prints that expression, anything else is parsed as a program.
+fn parse(source: &str, path: &str) -> Result<(), RunError> {
+    let renderer = renderer(source, Some(path));
+    let tokens = scan(source, &renderer)?;
+
+    if let Ok(expr) = Parser::new(tokens.clone()).parse_expression() {
+        println!("{}", expr);
+        return Ok(());
+    }
+
+    for stmt in parse_program(tokens, &renderer)? {
+        println!("{}", stmt);
+    }
+
+    Ok(())
+}
+
+/// Evaluates a single expression and prints its value
+fn evaluate(source: &str, path: &str) -> Result<(), RunError> {
+    let renderer = renderer(source, Some(path));
+    let program =
+        Program::compile_expression(source).map_err(|error| lox_error(error, &renderer))?;
+
+    let value = Lox::new()
+        .execute(program)
+        .map_err(|error| lox_error(error, &renderer))?;
+
+    println!("{}", value);
+
+    Ok(())
+}
+
+fn run(source: &str, path: Option<&str>, mut engine: Engine) -> Result<(), RunError> {
+    let renderer = renderer(source, path);
+    let program = Program::compile(source).map_err(|error| lox_error(error, &renderer))?;
+
+    execute(&mut engine, program, &renderer)
+}
+
+/// Runs a compiled program on the given engine
+fn execute(engine: &mut Engine, program: Program, renderer: &Renderer) -> Result<(), RunError> {
+    match engine {
+        Engine::TreeWalker(lox) => lox
+            .execute(program)
+            .map(|_| ())
+            .map_err(|error| lox_error(error, renderer)),
+        Engine::Vm { vm, dump_bytecode } => {
+            let script = Compiler::new(vm.heap_mut())
+                .compile(&program.statements)
+                .map_err(|errors| {
+                    for error in &errors {
+                        report(renderer, Diagnostic::from(error));
+                    }
+                    RunError::Compile
+                })?;
+
+            if *dump_bytecode {
+                match write!(stdout().lock(), "{}", disassemble(vm.heap(), script)) {
+                    Ok(()) => {}
+                    // Nothing reads the output anymore, such as `head`
+                    // having seen enough of the dump
+                    Err(error) if error.kind() == ErrorKind::BrokenPipe => return Ok(()),
+                    Err(error) => return Err(output_error(error)),
+                }
+            }
+
+            vm.interpret(script)
+                .map_err(|error| runtime_error(&error, renderer))
+        }
+    }
+}
+
+/// Reports the errors of a failed run, unless it is the program asking to exit
+fn lox_error(error: LoxError, renderer: &Renderer) -> RunError {
+    if let LoxError::Runtime(error) = &error {
+        match error.typ() {
+            // Nothing reads the output anymore, such as `head` having seen
+            // enough of it, so the program stops as if it had exited
+            RuntimeErrorType::Output(ErrorKind::BrokenPipe) => return RunError::Exit(0),
+            RuntimeErrorType::Output(kind) => return output_error(io::Error::from(*kind)),
+            _ => {}
+        }
+    }
+
+    for diagnostic in error.diagnostics() {
+        report(renderer, diagnostic);
+    }
+
+    match error {
+        LoxError::Io(error) => {
+            eprintln!("{}", error);
+            RunError::Input
+        }
+        LoxError::Compile(_) => RunError::Compile,
+        LoxError::Runtime(_) => RunError::Runtime,
+        LoxError::Exit(code) => RunError::Exit(code),
+    }
+}
+
+fn output_error(error: io::Error) -> RunError {
+    eprintln!("Could not write output: {}", error);
+    RunError::Output
+}
+
+/// Reports a runtime error, unless it is the program asking to exit
+fn runtime_error(error: &RuntimeError, renderer: &Renderer) -> RunError {
+    lox_error(LoxError::from(error.clone()), renderer)
+}
+
+fn scan<'src>(source: &'src str, renderer: &Renderer) -> Result<Vec<Token<'src>>, RunError> {
     let mut lexer = Lexer::new(source);
     let (tokens, lexical_errors) = lexer.scan_tokens();
 
-    for error in lexical_errors {
-        eprintln!("{}", error);
+    for error in &lexical_errors {
+        report(renderer, Diagnostic::from(error));
+    }
+
+    if lexical_errors.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(RunError::Compile)
+    }
+}
+
+fn parse_program(tokens: Vec<Token>, renderer: &Renderer) -> Result<Vec<Stmt>, RunError> {
+    let (statements, parse_errors) = Parser::new(tokens).parse();
+
+    for error in &parse_errors {
+        report(renderer, Diagnostic::from(error));
+    }
+
+    if parse_errors.is_empty() {
+        Ok(statements)
+    } else {
+        Err(RunError::Compile)
+    }
+}
+
+fn renderer<'a>(source: &'a str, path: Option<&'a str>) -> Renderer<'a> {
+    Renderer::new(source, path, stderr().is_terminal())
+}
+
+fn report(renderer: &Renderer, diagnostic: Diagnostic) {
+    eprintln!("{}\n", renderer.render(&diagnostic));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_input_completeness() {
+        assert!(is_input_complete("print 1;\n"));
+        assert!(is_input_complete("1 + 2\n"));
+        assert!(is_input_complete("fun f() {\n  return (1);\n}\n"));
+        assert!(!is_input_complete("fun f() {\n"));
+        assert!(!is_input_complete("print (1 +\n"));
+        assert!(!is_input_complete("print \"multi\nline\n"));
+        assert!(!is_input_complete("{ print \"}\"; \n"));
+        assert!(!is_input_complete("print \"${f(\n"));
+        assert!(!is_input_complete("/* a /* nested */ comment\n"));
+
+        // Too many closing brackets can never be fixed by reading more
+        assert!(is_input_complete("}\n"));
+    }
+
+    #[test]
+    fn test_command_without_script() {
+        let options = Options::default();
+
+        for command in ["tokenize", "parse", "evaluate", "run"] {
+            let result = run_command(&[command], &options);
+            assert_eq!(result.err().map(|error| error.exit_code()), Some(64));
+        }
     }
 
-    for token in tokens {
-        println!("{}", token);
+    #[test]
+    fn test_error_in_function_from_earlier_input() {
+        for vm in [false, true] {
+            let mut engine = Engine::new(&Options {
+                vm,
+                ..Options::default()
+            });
+            let mut session = String::new();
+
+            let inputs = [
+                "fun f() { var xxxxxxxxxxxxxxxxxxxx = 1; return -\"a\"; }\n",
+                "f();\n",
+            ];
+            let results = inputs.map(|input| {
+                let offset = session.len();
+                session.push_str(input);
+                run_input(&mut engine, &session, offset).map_err(|error| error.exit_code())
+            });
+
+            assert_eq!(results, [Ok(()), Err(70)]);
+        }
     }
 }
@@ -0,0 +1,110 @@
+use crate::diagnostics::Diagnostic;
+use crate::lexical_analysis::span::Span;
+
+#[derive(Debug, Clone)]
+pub struct ResolveError {
+    typ: ResolveErrorType,
+    /// The lexeme of the token the error was found at.
+    lexeme: String,
+    span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ResolveErrorType {
+    ReadInOwnInitializer,
+    AlreadyDeclared,
+    TopLevelReturn,
+    ReturnValueFromInitializer,
+    ThisOutsideClass,
+    SuperOutsideClass,
+    SuperWithoutSuperclass,
+    InheritsFromItself,
+}
+
+impl ResolveError {
+    pub fn new(typ: ResolveErrorType, lexeme: String, span: Span) -> Self {
+        Self { typ, lexeme, span }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl ResolveErrorType {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ResolveErrorType::ReadInOwnInitializer => "E200",
+            ResolveErrorType::AlreadyDeclared => "E201",
+            ResolveErrorType::TopLevelReturn => "E202",
+            ResolveErrorType::ReturnValueFromInitializer => "E203",
+            ResolveErrorType::ThisOutsideClass => "E204",
+            ResolveErrorType::SuperOutsideClass => "E205",
+            ResolveErrorType::SuperWithoutSuperclass => "E206",
+            ResolveErrorType::InheritsFromItself => "E207",
+        }
+    }
+
+    pub fn help(&self) -> Option<&'static str> {
+        match self {
+            ResolveErrorType::ReadInOwnInitializer => {
+                Some("rename the new variable, or read the outer one before shadowing it")
+            }
+            ResolveErrorType::AlreadyDeclared => {
+                Some("assign to the existing variable instead of declaring it again")
+            }
+            ResolveErrorType::ReturnValueFromInitializer => {
+                Some("'init' always returns the instance; use a bare 'return;'")
+            }
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ResolveErrorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResolveErrorType::ReadInOwnInitializer => {
+                write!(f, "Can't read local variable in its own initializer.")
+            }
+            ResolveErrorType::AlreadyDeclared => {
+                write!(f, "Already a variable with this name in this scope.")
+            }
+            ResolveErrorType::TopLevelReturn => write!(f, "Can't return from top-level code."),
+            ResolveErrorType::ReturnValueFromInitializer => {
+                write!(f, "Can't return a value from an initializer.")
+            }
+            ResolveErrorType::ThisOutsideClass => {
+                write!(f, "Can't use 'this' outside of a class.")
+            }
+            ResolveErrorType::SuperOutsideClass => {
+                write!(f, "Can't use 'super' outside of a class.")
+            }
+            ResolveErrorType::SuperWithoutSuperclass => {
+                write!(f, "Can't use 'super' in a class with no superclass.")
+            }
+            ResolveErrorType::InheritsFromItself => {
+                write!(f, "A class can't inherit from itself.")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[line {}] Error at '{}': {}",
+            self.span.line(),
+            self.lexeme,
+            self.typ
+        )
+    }
+}
+
+impl From<&ResolveError> for Diagnostic {
+    fn from(error: &ResolveError) -> Self {
+        Diagnostic::new(error.typ.code(), error.typ.to_string(), error.span())
+            .with_help(error.typ.help())
+    }
+}
@@ -0,0 +1,483 @@
+use crate::lexical_analysis::symbol::Symbol;
+use crate::lexical_analysis::token::Token;
+use crate::semantic_analysis::error::{ResolveError, ResolveErrorType};
+use crate::syntax_analysis::expression::{Expr, ExprId};
+use crate::syntax_analysis::statement::{Function, Stmt};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// A static pass over the AST which works out, for every expression referring
+/// to a local variable, how many scopes separate it from the variable's
+/// declaration. Variables that are not found in any scope are globals.
+pub struct Resolver {
+    /// The stack of local scopes, mapping each declared name to whether its
+    /// initializer has finished, i.e. whether the variable is ready for use.
+    scopes: Vec<HashMap<Symbol, bool>>,
+    /// The scope depths found so far.
+    locals: HashMap<ExprId, usize>,
+    errors: Vec<ResolveError>,
+    /// The kind of function whose body is currently being resolved.
+    current_function: FunctionType,
+    /// The kind of class whose body is currently being resolved.
+    current_class: ClassType,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            scopes: Vec::new(),
+            locals: HashMap::new(),
+            errors: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+        }
+    }
+
+    /// Resolves a whole program, returning the scope depth of every local
+    /// variable reference along with any semantic errors
+    pub fn resolve(mut self, statements: &[Stmt]) -> (HashMap<ExprId, usize>, Vec<ResolveError>) {
+        self.resolve_statements(statements);
+
+        (self.locals, self.errors)
+    }
+
+    fn resolve_statements(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            self.resolve_statement(stmt);
+        }
+    }
+
+    fn resolve_statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block { statements } => {
+                self.begin_scope();
+                self.resolve_statements(statements);
+                self.end_scope();
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
+                let enclosing_class = self.current_class;
+                self.current_class = ClassType::Class;
+
+                self.declare(name);
+                self.define(name);
+
+                if let Some(superclass) = superclass {
+                    if superclass.name.symbol() == name.symbol() {
+                        self.error(ResolveErrorType::InheritsFromItself, &superclass.name);
+                    }
+
+                    self.current_class = ClassType::Subclass;
+                    self.resolve_local(superclass.id, &superclass.name);
+
+                    self.begin_scope();
+                    self.define_name(Symbol::SUPER);
+                }
+
+                self.begin_scope();
+                self.define_name(Symbol::THIS);
+
+                for method in methods {
+                    let typ = if method.name.symbol() == Symbol::INIT {
+                        FunctionType::Initializer
+                    } else {
+                        FunctionType::Method
+                    };
+
+                    self.resolve_function(method, typ);
+                }
+
+                self.end_scope();
+
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+
+                self.current_class = enclosing_class;
+            }
+            Stmt::Expression { expr } | Stmt::Print { expr } => self.resolve_expression(expr),
+            Stmt::Function(function) => {
+                // Define the name eagerly so the function can refer to itself
+                self.declare(&function.name);
+                self.define(&function.name);
+
+                self.resolve_function(function, FunctionType::Function);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_statement(else_branch);
+                }
+            }
+            Stmt::Return { keyword, value } => {
+                if self.current_function == FunctionType::None {
+                    self.error(ResolveErrorType::TopLevelReturn, keyword);
+                }
+
+                if let Some(value) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        self.error(ResolveErrorType::ReturnValueFromInitializer, keyword);
+                    }
+
+                    self.resolve_expression(value);
+                }
+            }
+            Stmt::Var { name, initializer } => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.resolve_expression(initializer);
+                }
+                self.define(name);
+            }
+            Stmt::While { condition, body } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(body);
+            }
+        }
+    }
+
+    fn resolve_function(&mut self, function: &Function, typ: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = typ;
+
+        self.begin_scope();
+        for parameter in &function.arguments {
+            self.declare(parameter);
+            self.define(parameter);
+        }
+        self.resolve_statements(&function.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    fn resolve_expression(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Assign { name, value, id } => {
+                self.resolve_expression(value);
+                self.resolve_local(*id, name);
+            }
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.resolve_expression(left);
+                self.resolve_expression(right);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.resolve_expression(callee);
+                for argument in arguments {
+                    self.resolve_expression(argument);
+                }
+            }
+            Expr::Get { object, .. } => self.resolve_expression(object),
+            Expr::Group { expression } => self.resolve_expression(expression),
+            Expr::Interpolation { parts, .. } => {
+                for part in parts {
+                    self.resolve_expression(part);
+                }
+            }
+            Expr::Literal { .. } => {}
+            Expr::Set { object, value, .. } => {
+                self.resolve_expression(value);
+                self.resolve_expression(object);
+            }
+            Expr::Super { keyword, id, .. } => {
+                match self.current_class {
+                    ClassType::None => self.error(ResolveErrorType::SuperOutsideClass, keyword),
+                    ClassType::Class => {
+                        self.error(ResolveErrorType::SuperWithoutSuperclass, keyword)
+                    }
+                    ClassType::Subclass => {}
+                }
+
+                self.resolve_local(*id, keyword);
+            }
+            Expr::This { keyword, id } => {
+                if self.current_class == ClassType::None {
+                    self.error(ResolveErrorType::ThisOutsideClass, keyword);
+                    return;
+                }
+
+                self.resolve_local(*id, keyword);
+            }
+            Expr::Unary { right, .. } => self.resolve_expression(right),
+            Expr::Variable { name } => {
+                if self
+                    .scopes
+                    .last()
+                    .and_then(|scope| scope.get(&name.name.symbol()))
+                    == Some(&false)
+                {
+                    self.error(ResolveErrorType::ReadInOwnInitializer, &name.name);
+                }
+
+                self.resolve_local(name.id, &name.name);
+            }
+        }
+    }
+
+    /// Records how many scopes up the variable is declared, if it is a local
+    fn resolve_local(&mut self, id: ExprId, name: &Token) {
+        if let Some(depth) = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(&name.symbol()))
+        {
+            self.locals.insert(id, depth);
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Adds a variable to the innermost scope, marked as not yet initialized
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+
+        if scope.insert(name.symbol(), false).is_some() {
+            self.error(ResolveErrorType::AlreadyDeclared, name);
+        }
+    }
+
+    /// Marks a declared variable in the innermost scope as ready for use
+    fn define(&mut self, name: &Token) {
+        self.define_name(name.symbol());
+    }
+
+    fn define_name(&mut self, name: Symbol) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, true);
+        }
+    }
+
+    fn error(&mut self, typ: ResolveErrorType, token: &Token) {
+        self.errors.push(ResolveError::new(
+            typ,
+            token.lexeme.to_string(),
+            token.span(),
+        ));
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::diagnostics::Diagnostic;
+    use crate::lexical_analysis::lexer::Lexer;
+    use crate::lexical_analysis::span::Span;
+    use crate::syntax_analysis::parser::Parser;
+
+    fn resolve(source: &str) -> (HashMap<ExprId, usize>, Vec<ResolveError>) {
+        let mut lexer = Lexer::new(source);
+        let (tokens, errors) = lexer.scan_tokens();
+        assert!(errors.is_empty(), "Unexpected lexical errors: {:?}", errors);
+
+        let (statements, errors) = Parser::new(tokens).parse();
+        assert!(errors.is_empty(), "Unexpected parse errors: {:?}", errors);
+
+        Resolver::new().resolve(&statements)
+    }
+
+    fn assert_errors(source: &str, expected: &[&str]) {
+        let (_, errors) = resolve(source);
+
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_scope_depths() {
+        let (locals, errors) = resolve(
+            "var global = 1;
+             fun outer(a) {
+                 { var b = a; fun inner() { return a + b + global; } }
+             }",
+        );
+
+        assert!(errors.is_empty(), "Unexpected resolve errors: {:?}", errors);
+
+        // `a` in `var b = a`, plus `a` and `b` inside `inner`. The reference
+        // to `global` is left for the interpreter to look up at runtime.
+        let mut depths = locals.values().copied().collect::<Vec<_>>();
+        depths.sort();
+        assert_eq!(depths, vec![1, 1, 2]);
+    }
+
+    #[test]
+    fn test_valid_programs() {
+        assert_errors(
+            "var a = 1; var a = a;
+             class A { init() { this.x = 1; return; } get() { return this.x; } }
+             class B < A { get() { return super.get(); } }
+             fun f() { return; }",
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_read_in_own_initializer() {
+        assert_errors(
+            "{ var a = a; }",
+            &["[line 1] Error at 'a': Can't read local variable in its own initializer."],
+        );
+        assert_errors(
+            "fun f() {\n  var b = 1 + b;\n}",
+            &["[line 2] Error at 'b': Can't read local variable in its own initializer."],
+        );
+
+        // Reading the outer variable before shadowing it is fine
+        assert_errors("{ var a = 1; { var b = a; var a = b; } }", &[]);
+    }
+
+    #[test]
+    fn test_already_declared() {
+        assert_errors(
+            "fun f() { var a; var a; }",
+            &["[line 1] Error at 'a': Already a variable with this name in this scope."],
+        );
+        assert_errors(
+            "fun f(a, a) {}",
+            &["[line 1] Error at 'a': Already a variable with this name in this scope."],
+        );
+
+        // Shadowing in an inner scope is not a redeclaration
+        assert_errors("fun f(a) { { var a = 1; } }", &[]);
+    }
+
+    #[test]
+    fn test_top_level_return() {
+        assert_errors(
+            "return 1;",
+            &["[line 1] Error at 'return': Can't return from top-level code."],
+        );
+        assert_errors(
+            "{\n  if (true) return;\n}",
+            &["[line 2] Error at 'return': Can't return from top-level code."],
+        );
+
+        let (_, errors) = resolve("var a = 1;\n  return;");
+        assert_eq!(errors[0].span(), Span::new(13, 19, 2, 3));
+    }
+
+    #[test]
+    fn test_return_value_from_initializer() {
+        assert_errors(
+            "class A { init() { return 1; } }",
+            &["[line 1] Error at 'return': Can't return a value from an initializer."],
+        );
+
+        // Only `init` itself is an initializer, not functions inside it
+        assert_errors(
+            "class A { init() { fun f() { return 1; } return; } other() { return 2; } }",
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_this_outside_class() {
+        assert_errors(
+            "print this;",
+            &["[line 1] Error at 'this': Can't use 'this' outside of a class."],
+        );
+        assert_errors(
+            "fun f() {\n  return this;\n}",
+            &["[line 2] Error at 'this': Can't use 'this' outside of a class."],
+        );
+
+        // Functions inside methods still see the class's `this`
+        assert_errors("class A { m() { fun f() { return this; } } }", &[]);
+    }
+
+    #[test]
+    fn test_super_outside_class() {
+        assert_errors(
+            "super.f();",
+            &["[line 1] Error at 'super': Can't use 'super' outside of a class."],
+        );
+        assert_errors(
+            "fun f() { return super.g; }",
+            &["[line 1] Error at 'super': Can't use 'super' outside of a class."],
+        );
+    }
+
+    #[test]
+    fn test_super_without_superclass() {
+        assert_errors(
+            "class A { f() { super.f(); } }",
+            &["[line 1] Error at 'super': Can't use 'super' in a class with no superclass."],
+        );
+
+        // A class nested in a subclass's method has no superclass of its own
+        assert_errors(
+            "class A {}\nclass B < A { m() { class C { n() { return super.m; } } } }",
+            &["[line 2] Error at 'super': Can't use 'super' in a class with no superclass."],
+        );
+    }
+
+    #[test]
+    fn test_inherits_from_itself() {
+        assert_errors(
+            "class A < A {}",
+            &["[line 1] Error at 'A': A class can't inherit from itself."],
+        );
+
+        let (_, errors) = resolve("class Node < Node {}");
+        assert_eq!(errors[0].span(), Span::new(13, 17, 1, 14));
+    }
+
+    #[test]
+    fn test_errors_are_all_reported() {
+        let (_, errors) = resolve("return;\nprint this;\nclass A < A {}");
+
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| Diagnostic::from(e).to_string())
+                .collect::<Vec<_>>(),
+            [
+                "error[E202]: Can't return from top-level code. at 1:1",
+                "error[E204]: Can't use 'this' outside of a class. at 2:7",
+                "error[E207]: A class can't inherit from itself. at 3:11",
+            ]
+        );
+    }
+}
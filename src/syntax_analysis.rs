@@ -1,2 +1,4 @@
+pub mod error;
 pub mod expression;
-pub mod statement;
\ No newline at end of file
+pub mod parser;
+pub mod statement;
@@ -0,0 +1,97 @@
+use crate::diagnostics::Diagnostic;
+use crate::lexical_analysis::span::Span;
+
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    typ: ParseErrorType,
+    /// The lexeme of the token the error was found at, or `None` if it was
+    /// found at the end of the input.
+    lexeme: Option<String>,
+    span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ParseErrorType {
+    /// A specific token was required, e.g. "Expect ')' after expression."
+    Expected(&'static str),
+    ExpectedExpression,
+    InvalidAssignmentTarget,
+    TooManyArguments,
+    TooManyParameters,
+}
+
+impl ParseError {
+    pub fn new(typ: ParseErrorType, lexeme: Option<String>, span: Span) -> Self {
+        Self { typ, lexeme, span }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl ParseErrorType {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseErrorType::Expected(_) => "E100",
+            ParseErrorType::ExpectedExpression => "E101",
+            ParseErrorType::InvalidAssignmentTarget => "E102",
+            ParseErrorType::TooManyArguments => "E103",
+            ParseErrorType::TooManyParameters => "E104",
+        }
+    }
+
+    pub fn help(&self) -> Option<&'static str> {
+        match self {
+            ParseErrorType::InvalidAssignmentTarget => {
+                Some("only variables and properties can be assigned to")
+            }
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ParseErrorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseErrorType::Expected(message) => write!(f, "Expect {}.", message),
+            ParseErrorType::ExpectedExpression => write!(f, "Expect expression."),
+            ParseErrorType::InvalidAssignmentTarget => write!(f, "Invalid assignment target."),
+            ParseErrorType::TooManyArguments => write!(f, "Can't have more than 255 arguments."),
+            ParseErrorType::TooManyParameters => {
+                write!(f, "Can't have more than 255 parameters.")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.lexeme {
+            Some(lexeme) => write!(
+                f,
+                "[line {}] Error at '{}': {}",
+                self.span.line(),
+                lexeme,
+                self.typ
+            ),
+            None => write!(f, "[line {}] Error at end: {}", self.span.line(), self.typ),
+        }
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        let found = match &error.lexeme {
+            Some(lexeme) => format!("'{}'", lexeme),
+            None => "end of input".to_string(),
+        };
+
+        Diagnostic::new(
+            error.typ.code(),
+            format!("{} Found {}.", error.typ, found),
+            error.span(),
+        )
+        .with_help(error.typ.help())
+    }
+}
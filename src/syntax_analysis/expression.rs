@@ -1,72 +1,119 @@
+use crate::lexical_analysis::span::Span;
 use crate::lexical_analysis::token::{Literal, Token};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// Expressions evaluate to values and exist as part of a statement
 #[allow(unused)]
+#[derive(Debug)]
 pub enum Expr {
     /// Variable assignments: x = 42
     Assign {
-        name: Token,
+        name: Token<'static>,
         value: Box<Expr>,
+        id: ExprId,
     },
     /// Binary operations: 1 + 2
     Binary {
         left: Box<Expr>,
-        operator: Token,
+        operator: Token<'static>,
         right: Box<Expr>,
     },
     /// Function calls: foo()
     Call {
         callee: Box<Expr>,
-        parenthesis: Token,
+        parenthesis: Token<'static>,
         arguments: Vec<Expr>,
     },
     /// Property accesses: object.name
     Get {
         object: Box<Expr>,
-        name: Token,
+        name: Token<'static>,
     },
     /// Groupings: (1 + 2)
-    Group {
-        expression: Box<Expr>,
-    },
+    Group { expression: Box<Expr> },
+    /// Strings with interpolated expressions: "Hello ${name}!". The parts are
+    /// converted to strings the way `print` shows them and concatenated.
+    Interpolation { parts: Vec<Expr>, span: Span },
     /// Literals: numbers, strings, and so on
-    Literal {
-        value: Option<Literal>,
-    },
+    Literal { value: Option<Literal>, span: Span },
     /// Logical operations: x and y
     Logical {
         left: Box<Expr>,
-        operator: Token,
+        operator: Token<'static>,
         right: Box<Expr>,
     },
     /// Property assignment: object.name = value
     Set {
         object: Box<Expr>,
-        name: Token,
+        name: Token<'static>,
         value: Box<Expr>,
     },
     /// Super class access: super.method()
     Super {
-        keyword: Token,
-        method: Token,
+        keyword: Token<'static>,
+        method: Token<'static>,
+        id: ExprId,
     },
     /// this keyword
-    This {
-        keyword: Token,
-    },
+    This { keyword: Token<'static>, id: ExprId },
     /// Unary operations: -42
     Unary {
-        operator: Token,
+        operator: Token<'static>,
         right: Box<Expr>,
     },
     /// Variable references: x
-    Variable {
-        name: Variable,
-    },
+    Variable { name: Variable },
 }
 
+impl Expr {
+    /// Where errors about the expression are reported: at its operator, or
+    /// the token it is named by
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Assign { name, .. } | Expr::Get { name, .. } | Expr::Set { name, .. } => {
+                name.span()
+            }
+            Expr::Binary { operator, .. }
+            | Expr::Logical { operator, .. }
+            | Expr::Unary { operator, .. } => operator.span(),
+            Expr::Call { parenthesis, .. } => parenthesis.span(),
+            Expr::Group { expression } => expression.span(),
+            Expr::Interpolation { span, .. } | Expr::Literal { span, .. } => *span,
+            Expr::Super { keyword, .. } | Expr::This { keyword, .. } => keyword.span(),
+            Expr::Variable { name } => name.name.span(),
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct Variable {
-    pub name: Token,
+    pub name: Token<'static>,
+    pub id: ExprId,
+}
+
+impl Variable {
+    pub fn new(name: Token<'static>) -> Self {
+        Self {
+            name,
+            id: ExprId::new(),
+        }
+    }
+}
+
+/// Identifies an expression which refers to a variable, so that the resolver
+/// can tell the interpreter which scope that variable lives in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(usize);
+
+impl ExprId {
+    /// Creates an id which is unique for the whole run of the program, even
+    /// across separately parsed pieces of source code
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
 }
 
 impl std::fmt::Display for Variable {
@@ -78,7 +125,7 @@ impl std::fmt::Display for Variable {
 impl std::fmt::Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Expr::Assign { name, value } => {
+            Expr::Assign { name, value, .. } => {
                 write!(f, "(= {} {})", name.lexeme, value)
             }
             Expr::Binary {
@@ -103,7 +150,14 @@ impl std::fmt::Display for Expr {
             Expr::Group { expression } => {
                 write!(f, "(group {})", expression)
             }
-            Expr::Literal { value } => match value {
+            Expr::Interpolation { parts, .. } => {
+                write!(f, "(interpolate")?;
+                for part in parts {
+                    write!(f, " {}", part)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Literal { value, .. } => match value {
                 None => {
                     write!(f, "nil")
                 }
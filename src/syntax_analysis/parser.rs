@@ -0,0 +1,893 @@
+use crate::lexical_analysis::token::{Literal, Token};
+use crate::lexical_analysis::token_type::TokenType;
+use crate::syntax_analysis::error::{ParseError, ParseErrorType};
+use crate::syntax_analysis::expression::{Expr, ExprId, Variable};
+use crate::syntax_analysis::statement::{Function, Stmt};
+use std::rc::Rc;
+
+/// The maximum number of arguments a call, or parameters a function, may have
+const MAX_ARITY: usize = 255;
+
+#[derive(Clone, Copy)]
+enum FunctionKind {
+    Function,
+    Method,
+}
+
+/// A recursive descent parser which turns the lexer's tokens into statements.
+/// Tokens are taken one at a time as parsing goes, so the lexer can stream
+/// them rather than scanning the whole source first.
+pub struct Parser<'src, I: Iterator<Item = Token<'src>>> {
+    /// The rest of the tokens to parse, always terminated by an `Eof` token.
+    /// They borrow from the source, and only the tokens kept in the syntax
+    /// tree, which outlives the source, are detached from it.
+    tokens: I,
+    /// The token currently being looked at.
+    current: Token<'src>,
+    /// The token before it, once there is one.
+    previous: Option<Token<'src>>,
+    /// The syntax errors found so far.
+    errors: Vec<ParseError>,
+}
+
+impl<'src, I: Iterator<Item = Token<'src>>> Parser<'src, I> {
+    pub fn new(tokens: impl IntoIterator<IntoIter = I>) -> Self {
+        let mut tokens = tokens.into_iter();
+
+        Self {
+            current: tokens.next().expect("tokens end with Eof"),
+            tokens,
+            previous: None,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Parses a whole program. After a syntax error the parser skips ahead to
+    /// the next statement and carries on, so every error in the program is
+    /// reported, while the statements containing errors are left out.
+    pub fn parse(&mut self) -> (Vec<Stmt>, Vec<ParseError>) {
+        let mut statements = Vec::new();
+
+        while !self.is_at_end() {
+            if let Some(stmt) = self.declaration() {
+                statements.push(stmt);
+            }
+        }
+
+        (statements, std::mem::take(&mut self.errors))
+    }
+
+    /// Parses a single expression which must make up the whole input
+    pub fn parse_expression(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.expression()?;
+        self.consume(TokenType::Eof, "end of expression")?;
+
+        Ok(expr)
+    }
+
+    /// Parses a declaration, or records the syntax error in it and
+    /// synchronizes with the start of the next statement
+    fn declaration(&mut self) -> Option<Stmt> {
+        match self.try_declaration() {
+            Ok(stmt) => Some(stmt),
+            Err(error) => {
+                self.errors.push(error);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn try_declaration(&mut self) -> Result<Stmt, ParseError> {
+        if self.advance_if_matches(&[TokenType::Class]) {
+            self.class_declaration()
+        } else if self.advance_if_matches(&[TokenType::Fun]) {
+            Ok(Stmt::Function(Rc::new(
+                self.function(FunctionKind::Function)?,
+            )))
+        } else if self.advance_if_matches(&[TokenType::Var]) {
+            self.var_declaration()
+        } else {
+            self.statement()
+        }
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let doc = self.previous().doc().cloned();
+        let name = self
+            .consume(TokenType::Identifier, "class name")?
+            .into_owned();
+
+        let superclass = if self.advance_if_matches(&[TokenType::Less]) {
+            let name = self
+                .consume(TokenType::Identifier, "superclass name")?
+                .into_owned();
+            Some(Variable::new(name))
+        } else {
+            None
+        };
+
+        self.consume(TokenType::LeftBrace, "'{' before class body")?;
+
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(Rc::new(self.function(FunctionKind::Method)?));
+        }
+
+        self.consume(TokenType::RightBrace, "'}' after class body")?;
+
+        Ok(Stmt::Class {
+            name,
+            superclass,
+            methods,
+            doc,
+        })
+    }
+
+    /// Parses a function or method declaration after its `fun` keyword, if any
+    fn function(&mut self, kind: FunctionKind) -> Result<Function, ParseError> {
+        let (name_expected, parenthesis_expected, body_expected) = match kind {
+            FunctionKind::Function => (
+                "function name",
+                "'(' after function name",
+                "'{' before function body",
+            ),
+            FunctionKind::Method => (
+                "method name",
+                "'(' after method name",
+                "'{' before method body",
+            ),
+        };
+
+        // Doc comments come before the `fun` keyword, or a method's name
+        let doc = match kind {
+            FunctionKind::Function => self.previous().doc().cloned(),
+            FunctionKind::Method => self.peek().doc().cloned(),
+        };
+
+        let name = self
+            .consume(TokenType::Identifier, name_expected)?
+            .into_owned();
+        self.consume(TokenType::LeftParen, parenthesis_expected)?;
+
+        let mut arguments = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARITY {
+                    return Err(self.error(ParseErrorType::TooManyParameters));
+                }
+
+                arguments.push(
+                    self.consume(TokenType::Identifier, "parameter name")?
+                        .into_owned(),
+                );
+
+                if !self.advance_if_matches(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(TokenType::RightParen, "')' after parameters")?;
+
+        self.consume(TokenType::LeftBrace, body_expected)?;
+        let body = self.block()?;
+
+        Ok(Function {
+            name,
+            arguments,
+            body,
+            doc,
+        })
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let name = self
+            .consume(TokenType::Identifier, "variable name")?
+            .into_owned();
+
+        let initializer = if self.advance_if_matches(&[TokenType::Equal]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume(TokenType::Semicolon, "';' after variable declaration")?;
+
+        Ok(Stmt::Var { name, initializer })
+    }
+
+    fn statement(&mut self) -> Result<Stmt, ParseError> {
+        if self.advance_if_matches(&[TokenType::For]) {
+            self.for_statement()
+        } else if self.advance_if_matches(&[TokenType::If]) {
+            self.if_statement()
+        } else if self.advance_if_matches(&[TokenType::Print]) {
+            self.print_statement()
+        } else if self.advance_if_matches(&[TokenType::Return]) {
+            self.return_statement()
+        } else if self.advance_if_matches(&[TokenType::While]) {
+            self.while_statement()
+        } else if self.advance_if_matches(&[TokenType::LeftBrace]) {
+            Ok(Stmt::Block {
+                statements: self.block()?,
+            })
+        } else {
+            self.expression_statement()
+        }
+    }
+
+    /// There is no `for` statement in the AST. A `for` loop is desugared into
+    /// an optional initializer followed by a `while` loop whose body runs the
+    /// increment after the original body.
+    fn for_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().span();
+        self.consume(TokenType::LeftParen, "'(' after 'for'")?;
+
+        let initializer = if self.advance_if_matches(&[TokenType::Semicolon]) {
+            None
+        } else if self.advance_if_matches(&[TokenType::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if self.check(TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::Semicolon, "';' after loop condition")?;
+
+        let increment = if self.check(TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::RightParen, "')' after for clauses")?;
+
+        let mut body = self.statement()?;
+
+        if let Some(increment) = increment {
+            body = Stmt::Block {
+                statements: vec![body, Stmt::Expression { expr: increment }],
+            };
+        }
+
+        body = Stmt::While {
+            condition: condition.unwrap_or(Expr::Literal {
+                value: Some(Literal::Boolean(true)),
+                span: keyword,
+            }),
+            body: Box::new(body),
+        };
+
+        if let Some(initializer) = initializer {
+            body = Stmt::Block {
+                statements: vec![initializer, body],
+            };
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParen, "'(' after 'if'")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "')' after if condition")?;
+
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = if self.advance_if_matches(&[TokenType::Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+
+        Ok(Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt, ParseError> {
+        let expr = self.expression()?;
+        self.consume(TokenType::Semicolon, "';' after value")?;
+
+        Ok(Stmt::Print { expr })
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone().into_owned();
+
+        let value = if self.check(TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::Semicolon, "';' after return value")?;
+
+        Ok(Stmt::Return { keyword, value })
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParen, "'(' after 'while'")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "')' after condition")?;
+
+        let body = Box::new(self.statement()?);
+
+        Ok(Stmt::While { condition, body })
+    }
+
+    /// Parses the statements of a block whose opening brace was already consumed
+    fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        let mut statements = Vec::new();
+
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if let Some(stmt) = self.declaration() {
+                statements.push(stmt);
+            }
+        }
+
+        self.consume(TokenType::RightBrace, "'}' after block")?;
+
+        Ok(statements)
+    }
+
+    fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
+        let expr = self.expression()?;
+        self.consume(TokenType::Semicolon, "';' after expression")?;
+
+        Ok(Stmt::Expression { expr })
+    }
+
+    fn expression(&mut self) -> Result<Expr, ParseError> {
+        self.assignment()
+    }
+
+    /// The left-hand side of an assignment is parsed as an ordinary expression
+    /// first, and only turned into an assignment target once we see the `=`.
+    fn assignment(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.or()?;
+
+        if self.advance_if_matches(&[TokenType::Equal]) {
+            let equals = self.previous().clone();
+            let value = Box::new(self.assignment()?);
+
+            return match expr {
+                Expr::Variable { name } => Ok(Expr::Assign {
+                    name: name.name,
+                    value,
+                    id: name.id,
+                }),
+                Expr::Get { object, name } => Ok(Expr::Set {
+                    object,
+                    name,
+                    value,
+                }),
+                _ => Err(Self::error_at(
+                    &equals,
+                    ParseErrorType::InvalidAssignmentTarget,
+                )),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.and()?;
+
+        while self.advance_if_matches(&[TokenType::Or]) {
+            let operator = self.previous().clone().into_owned();
+            let right = Box::new(self.and()?);
+            expr = Expr::Logical {
+                left: Box::new(expr),
+                operator,
+                right,
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.equality()?;
+
+        while self.advance_if_matches(&[TokenType::And]) {
+            let operator = self.previous().clone().into_owned();
+            let right = Box::new(self.equality()?);
+            expr = Expr::Logical {
+                left: Box::new(expr),
+                operator,
+                right,
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> Result<Expr, ParseError> {
+        self.binary(
+            &[TokenType::BangEqual, TokenType::EqualEqual],
+            Self::comparison,
+        )
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ParseError> {
+        self.binary(
+            &[
+                TokenType::Greater,
+                TokenType::GreaterEqual,
+                TokenType::Less,
+                TokenType::LessEqual,
+            ],
+            Self::term,
+        )
+    }
+
+    fn term(&mut self) -> Result<Expr, ParseError> {
+        self.binary(&[TokenType::Minus, TokenType::Plus], Self::factor)
+    }
+
+    fn factor(&mut self) -> Result<Expr, ParseError> {
+        self.binary(&[TokenType::Slash, TokenType::Star], Self::unary)
+    }
+
+    /// Parses a left-associative chain of binary operators of one precedence
+    /// level, with `operand` parsing the next higher level.
+    fn binary(
+        &mut self,
+        operators: &[TokenType],
+        operand: fn(&mut Self) -> Result<Expr, ParseError>,
+    ) -> Result<Expr, ParseError> {
+        let mut expr = operand(self)?;
+
+        while self.advance_if_matches(operators) {
+            let operator = self.previous().clone().into_owned();
+            let right = Box::new(operand(self)?);
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right,
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if self.advance_if_matches(&[TokenType::Bang, TokenType::Minus]) {
+            let operator = self.previous().clone().into_owned();
+            let right = Box::new(self.unary()?);
+            return Ok(Expr::Unary { operator, right });
+        }
+
+        self.call()
+    }
+
+    fn call(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.advance_if_matches(&[TokenType::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.advance_if_matches(&[TokenType::Dot]) {
+                let name = self
+                    .consume(TokenType::Identifier, "property name after '.'")?
+                    .into_owned();
+                expr = Expr::Get {
+                    object: Box::new(expr),
+                    name,
+                };
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, ParseError> {
+        let mut arguments = Vec::new();
+
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARITY {
+                    return Err(self.error(ParseErrorType::TooManyArguments));
+                }
+
+                arguments.push(self.expression()?);
+
+                if !self.advance_if_matches(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        let parenthesis = self
+            .consume(TokenType::RightParen, "')' after arguments")?
+            .into_owned();
+
+        Ok(Expr::Call {
+            callee: Box::new(callee),
+            parenthesis,
+            arguments,
+        })
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let token = self.peek().clone();
+
+        let expr = match token.typ() {
+            TokenType::False => Expr::Literal {
+                value: Some(Literal::Boolean(false)),
+                span: token.span(),
+            },
+            TokenType::True => Expr::Literal {
+                value: Some(Literal::Boolean(true)),
+                span: token.span(),
+            },
+            TokenType::Nil => Expr::Literal {
+                value: Some(Literal::Nil),
+                span: token.span(),
+            },
+            TokenType::Number | TokenType::String => Expr::Literal {
+                value: token.literal().cloned(),
+                span: token.span(),
+            },
+            TokenType::Interpolation => return self.interpolation(),
+            TokenType::Super => {
+                self.advance();
+                self.consume(TokenType::Dot, "'.' after 'super'")?;
+                let method = self
+                    .consume(TokenType::Identifier, "superclass method name")?
+                    .into_owned();
+                return Ok(Expr::Super {
+                    keyword: token.into_owned(),
+                    method,
+                    id: ExprId::new(),
+                });
+            }
+            TokenType::This => Expr::This {
+                keyword: token.into_owned(),
+                id: ExprId::new(),
+            },
+            TokenType::Identifier => Expr::Variable {
+                name: Variable::new(token.into_owned()),
+            },
+            TokenType::LeftParen => {
+                self.advance();
+                let expression = Box::new(self.expression()?);
+                self.consume(TokenType::RightParen, "')' after expression")?;
+                return Ok(Expr::Group { expression });
+            }
+            _ => return Err(self.error(ParseErrorType::ExpectedExpression)),
+        };
+
+        self.advance();
+
+        Ok(expr)
+    }
+
+    /// Parses a string with interpolated expressions, which the lexer split
+    /// into its parts around the expressions. Empty parts of the string are
+    /// left out.
+    fn interpolation(&mut self) -> Result<Expr, ParseError> {
+        let span = self.peek().span();
+        let mut parts = Vec::new();
+
+        loop {
+            let token = self.advance().clone();
+
+            if !matches!(token.literal(), Some(Literal::String(s)) if s.is_empty()) {
+                parts.push(Expr::Literal {
+                    value: token.literal().cloned(),
+                    span: token.span(),
+                });
+            }
+
+            if token.typ() == TokenType::String {
+                break;
+            }
+
+            parts.push(self.expression()?);
+
+            // The rest of the string starts at the brace closing the expression
+            let next = self.peek();
+            if !matches!(next.typ(), TokenType::String | TokenType::Interpolation)
+                || !next.lexeme.starts_with('}')
+            {
+                return Err(self.error(ParseErrorType::Expected(
+                    "'}' after interpolated expression",
+                )));
+            }
+        }
+
+        Ok(Expr::Interpolation { parts, span })
+    }
+
+    /// Consumes the current token if it has the given type, or fails with
+    /// "Expect {expected}." The token still borrows from the source.
+    fn consume(
+        &mut self,
+        typ: TokenType,
+        expected: &'static str,
+    ) -> Result<Token<'src>, ParseError> {
+        if self.check(typ) {
+            Ok(self.advance().clone())
+        } else {
+            Err(self.error(ParseErrorType::Expected(expected)))
+        }
+    }
+
+    /// Creates an error at the current token
+    fn error(&self, typ: ParseErrorType) -> ParseError {
+        Self::error_at(self.peek(), typ)
+    }
+
+    fn error_at(token: &Token, typ: ParseErrorType) -> ParseError {
+        let lexeme = match token.typ() {
+            TokenType::Eof => None,
+            _ => Some(token.lexeme.to_string()),
+        };
+
+        ParseError::new(typ, lexeme, token.span())
+    }
+
+    /// Discards tokens until the start of the next statement, which is either
+    /// just after a semicolon or at a keyword beginning a statement
+    fn synchronize(&mut self) {
+        self.advance();
+
+        while !self.is_at_end() {
+            if self.previous().typ() == TokenType::Semicolon {
+                return;
+            }
+
+            match self.peek().typ() {
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return => return,
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    fn advance_if_matches(&mut self, types: &[TokenType]) -> bool {
+        if types.iter().any(|&typ| self.check(typ)) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn check(&self, typ: TokenType) -> bool {
+        self.peek().typ() == typ
+    }
+
+    fn advance(&mut self) -> &Token<'src> {
+        if !self.is_at_end() {
+            let next = self.tokens.next().expect("tokens end with Eof");
+            self.previous = Some(std::mem::replace(&mut self.current, next));
+        }
+
+        self.previous()
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.peek().typ() == TokenType::Eof
+    }
+
+    fn peek(&self) -> &Token<'src> {
+        &self.current
+    }
+
+    fn previous(&self) -> &Token<'src> {
+        self.previous.as_ref().expect("a token has been consumed")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lexical_analysis::lexer::Lexer;
+
+    /// Parses the tokens as the lexer scans them
+    fn parse(source: &str) -> (Vec<Stmt>, Vec<ParseError>) {
+        let mut errors = Vec::new();
+        let result = Parser::new(Lexer::new(source).tokens(&mut errors)).parse();
+        assert!(errors.is_empty(), "Unexpected lexical errors: {:?}", errors);
+
+        result
+    }
+
+    fn assert_statements(source: &str, expected: &[&str]) {
+        let (statements, errors) = parse(source);
+
+        assert!(errors.is_empty(), "Unexpected parse errors: {:?}", errors);
+
+        let actual = statements
+            .iter()
+            .map(|stmt| stmt.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(actual, expected);
+    }
+
+    fn assert_errors(source: &str, expected: &[&str]) {
+        let (_, errors) = parse(source);
+
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_precedence_and_associativity() {
+        assert_statements(
+            "1 + 2 * 3 - 4 / -5;",
+            &["(; (- (+ 1.0 (* 2.0 3.0)) (/ 4.0 (- 5.0))))"],
+        );
+        assert_statements(
+            "a == b < c or !d and e;",
+            &["(; (or (== a (< b c)) (and (! d) e)))"],
+        );
+        assert_statements("(1 + 2) * 3;", &["(; (* (group (+ 1.0 2.0)) 3.0))"]);
+    }
+
+    #[test]
+    fn test_assignment_and_property_access() {
+        assert_statements("a = b = 1;", &["(; (= a (= b 1.0)))"]);
+        assert_statements(
+            "object.field.inner = foo(1, bar)(2);",
+            &["(; (= (. object field) inner (call (call foo 1.0 bar) 2.0)))"],
+        );
+        assert_statements("super.method(this);", &["(; (call (super method) this))"]);
+    }
+
+    #[test]
+    fn test_declarations() {
+        assert_statements("var a; var b = \"text\";", &["(var a)", "(var b = text)"]);
+        assert_statements(
+            "fun add(a, b) { return a + b; }",
+            &["(fun add(a b) (return (+ a b)))"],
+        );
+        assert_statements(
+            "class B < A { init() { this.x = nil; } }",
+            &["(class B < A (fun init () (; (= this x nil))))"],
+        );
+    }
+
+    #[test]
+    fn test_control_flow() {
+        assert_statements(
+            "if (a) print 1; else { print 2; }",
+            &["(if-else a (print 1.0) (block (print 2.0)))"],
+        );
+        assert_statements("while (true) return;", &["(while true (return))"]);
+    }
+
+    #[test]
+    fn test_for_loop_is_desugared() {
+        assert_statements(
+            "for (var i = 0; i < 3; i = i + 1) print i;",
+            &["(block (var i = 0.0) (while (< i 3.0) (block (print i) (; (= i (+ i 1.0))))))"],
+        );
+        assert_statements("for (;;) print 1;", &["(while true (print 1.0))"]);
+    }
+
+    #[test]
+    fn test_error_spans() {
+        let (_, errors) = parse("var a;\n  a + b = c;");
+
+        let span = errors[0].span();
+        assert_eq!((span.start(), span.end()), (15, 16));
+        assert_eq!((span.line(), span.column()), (2, 9));
+    }
+
+    #[test]
+    fn test_single_expression() {
+        let mut lexer = Lexer::new("(1 + 2) * -x");
+        let (tokens, _) = lexer.scan_tokens();
+        let expr = Parser::new(tokens).parse_expression().unwrap();
+        assert_eq!(expr.to_string(), "(* (group (+ 1.0 2.0)) (- x))");
+
+        let mut lexer = Lexer::new("1 + 2;");
+        let (tokens, _) = lexer.scan_tokens();
+        let error = Parser::new(tokens).parse_expression().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 1] Error at ';': Expect end of expression."
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_errors(
+            "print 1",
+            &["[line 1] Error at end: Expect ';' after value."],
+        );
+        assert_errors("1 + ;", &["[line 1] Error at ';': Expect expression."]);
+        assert_errors(
+            "a + b = c;",
+            &["[line 1] Error at '=': Invalid assignment target."],
+        );
+        assert_errors(
+            "fun f(a {}",
+            &["[line 1] Error at '{': Expect ')' after parameters."],
+        );
+    }
+
+    #[test]
+    fn test_recovers_after_errors() {
+        let (statements, errors) = parse(
+            "var = 1;
+             print 2;
+             fun f() { 1 + ; print 3; }
+             class { }
+             print 4",
+        );
+
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec![
+                "[line 1] Error at '=': Expect variable name.",
+                "[line 3] Error at ';': Expect expression.",
+                "[line 4] Error at '{': Expect class name.",
+                "[line 5] Error at end: Expect ';' after value.",
+            ]
+        );
+        assert_eq!(
+            statements
+                .iter()
+                .map(|stmt| stmt.to_string())
+                .collect::<Vec<_>>(),
+            vec!["(print 2.0)", "(fun f() (print 3.0))"]
+        );
+    }
+
+    #[test]
+    fn test_interpolation() {
+        assert_statements(
+            r#"print "a ${b} c${-d}"; print "${e}";"#,
+            &[
+                "(print (interpolate a  b  c (- d)))",
+                "(print (interpolate e))",
+            ],
+        );
+        assert_errors(
+            r#"print "${a b}";"#,
+            &["[line 1] Error at 'b': Expect '}' after interpolated expression."],
+        );
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let (statements, errors) = parse(
+            "/// A point.\nclass Point {\n  /// The length.\n  len() {}\n  other() {}\n}\n/// Says hi.\nfun hi() {}",
+        );
+        assert!(errors.is_empty(), "Unexpected parse errors: {:?}", errors);
+
+        let Stmt::Class { doc, methods, .. } = &statements[0] else {
+            panic!("Expected a class, got {}", statements[0]);
+        };
+        assert_eq!(doc.as_deref(), Some("A point."));
+        assert_eq!(methods[0].doc.as_deref(), Some("The length."));
+        assert_eq!(methods[1].doc, None);
+
+        let Stmt::Function(function) = &statements[1] else {
+            panic!("Expected a function, got {}", statements[1]);
+        };
+        assert_eq!(function.doc.as_deref(), Some("Says hi."));
+    }
+}
@@ -1,22 +1,26 @@
 use crate::lexical_analysis::token::Token;
 use crate::syntax_analysis::expression::{Expr, Variable};
+use std::rc::Rc;
 
 /// Statements do something like variable declarations, control flow, etc.
 /// They make up an Abstract Syntax Tree (AST)
 #[allow(unused)]
+#[derive(Debug)]
 pub enum Stmt {
     /// Code blocks
     Block { statements: Vec<Stmt> },
     /// Class declarations
     Class {
-        name: Token,
+        name: Token<'static>,
         superclass: Option<Variable>,
-        methods: Vec<Function>,
+        methods: Vec<Rc<Function>>,
+        /// The doc comment before the class, if any.
+        doc: Option<Rc<str>>,
     },
     /// Expression statements to handle expressions like: a + b; foo();
     Expression { expr: Expr },
-    /// Function declarations
-    Function(Function),
+    /// Function declarations, shared with the function values created from them
+    Function(Rc<Function>),
     /// If statements
     If {
         condition: Expr,
@@ -26,20 +30,26 @@ pub enum Stmt {
     /// Print statements
     Print { expr: Expr },
     /// Return statements inside a function body
-    Return { keyword: Token, value: Expr },
+    Return {
+        keyword: Token<'static>,
+        value: Option<Expr>,
+    },
     /// Variable declarations
     Var {
-        name: Token,
+        name: Token<'static>,
         initializer: Option<Expr>,
     },
     /// While loops
     While { condition: Expr, body: Box<Stmt> },
 }
 
+#[derive(Debug)]
 pub struct Function {
-    pub name: Token,
-    pub arguments: Vec<Token>,
+    pub name: Token<'static>,
+    pub arguments: Vec<Token<'static>>,
     pub body: Vec<Stmt>,
+    /// The doc comment before the function, if any.
+    pub doc: Option<Rc<str>>,
 }
 
 impl std::fmt::Display for Function {
@@ -77,6 +87,7 @@ impl std::fmt::Display for Stmt {
                 name,
                 superclass,
                 methods,
+                ..
             } => {
                 write!(f, "(class {}", name.lexeme)?;
                 if let Some(superclass) = superclass {
@@ -119,9 +130,10 @@ impl std::fmt::Display for Stmt {
             Stmt::Print { expr } => {
                 write!(f, "(print {})", expr)
             }
-            Stmt::Return { value, .. } => {
-                write!(f, "(return {})", value)
-            }
+            Stmt::Return { value, .. } => match value {
+                Some(value) => write!(f, "(return {})", value),
+                None => write!(f, "(return)"),
+            },
             Stmt::Var { name, initializer } => {
                 if let Some(init) = initializer {
                     write!(f, "(var {} = {})", name.lexeme, init)